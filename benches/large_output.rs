@@ -0,0 +1,65 @@
+// Times the steps that scale with the size of the output, on 100,000 rows of
+// `ls -l`-like output. Run with `cargo bench`.
+use std::{path::PathBuf, time::Instant};
+
+use lazycli::{app::App, args::Args, config::Config, parse};
+
+const ROW_COUNT: usize = 100_000;
+
+fn time(name: &str, f: &mut dyn FnMut()) {
+  let start = Instant::now();
+  f();
+  println!("{:<14} {:?}", name, start.elapsed());
+}
+
+fn main() {
+  let text = (0..ROW_COUNT)
+    .map(|i| {
+      format!(
+        "-rw-r--r-- {:>3} jesse staff {:>8} Oct 18 08:32 src/module_{}/file_{}.rs",
+        i % 7,
+        i * 37,
+        i % 100,
+        i
+      )
+    })
+    .collect::<Vec<String>>()
+    .join("\n");
+
+  let config = Config::new();
+  let args = Args {
+    command: Some(String::from("ls -l")),
+    profile: None,
+    lines_to_skip: 0,
+    refresh_frequency: 0.0,
+    format: None,
+    delimiter: None,
+    stream: false,
+    max_rows: None,
+  };
+  let mut app = App::new(&config, PathBuf::new(), args);
+  app.table.next();
+  let options = app.parse_options();
+
+  let mut parsed = None;
+  time("parse", &mut || {
+    parsed = Some(parse::parse(text.clone(), &options).unwrap())
+  });
+  time("update rows", &mut || {
+    app.update_rows(parsed.take().unwrap())
+  });
+  time("type filter", &mut || {
+    for c in "file_9".chars() {
+      app.push_filter_text_char(c);
+    }
+  });
+  time("clear filter", &mut || app.reset_filter_text());
+  time("move cursor", &mut || {
+    for _ in 0..1000 {
+      app.table.next();
+      app.get_selected_row();
+    }
+  });
+
+  assert_eq!(app.filtered_row_count(), ROW_COUNT);
+}
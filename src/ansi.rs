@@ -0,0 +1,258 @@
+use std::ops::Range;
+use tui::{
+  style::{Color, Modifier, Style},
+  text::{Span, Spans, Text},
+};
+
+// Splits text containing ANSI escape codes (as output by `ls --color` or `git
+// log --color`) into lines of styled spans. Colours carry over from one line to
+// the next, as they would in a terminal. Escape codes other than colours (e.g.
+// clearing the line, or hyperlinks) are dropped.
+pub fn parse_lines(text: &str) -> Vec<Vec<Span<'static>>> {
+  let mut style = Style::default();
+  text
+    .lines()
+    .map(|line| parse_line(line, &mut style))
+    .collect()
+}
+
+fn parse_line(line: &str, style: &mut Style) -> Vec<Span<'static>> {
+  let mut spans = vec![];
+  let mut current = String::new();
+  let mut chars = line.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    if c != '\x1b' {
+      current.push(c);
+      continue;
+    }
+
+    match chars.next() {
+      // CSI: parameters, then a final byte in the range @ to ~
+      Some('[') => {
+        let mut params = String::new();
+        let mut final_byte = None;
+        for c in chars.by_ref() {
+          if ('@'..='~').contains(&c) {
+            final_byte = Some(c);
+            break;
+          }
+          params.push(c);
+        }
+
+        if final_byte == Some('m') {
+          if !current.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut current), *style));
+          }
+          *style = apply_sgr(*style, &params);
+        }
+      }
+      // OSC: terminated by BEL or ESC \
+      Some(']') => {
+        while let Some(c) = chars.next() {
+          if c == '\x07' {
+            break;
+          }
+          if c == '\x1b' && chars.peek() == Some(&'\\') {
+            chars.next();
+            break;
+          }
+        }
+      }
+      // some other two-character sequence
+      _ => {}
+    }
+  }
+
+  if !current.is_empty() {
+    spans.push(Span::styled(current, *style));
+  }
+
+  spans
+}
+
+fn apply_sgr(style: Style, params: &str) -> Style {
+  // `ESC[m` is short for `ESC[0m`. Anything we can't make sense of is ignored.
+  let codes = params
+    .split(';')
+    .map(|code| match code {
+      "" => 0,
+      _ => code.parse::<u8>().unwrap_or(u8::MAX),
+    })
+    .collect::<Vec<u8>>();
+
+  let mut style = style;
+  let mut codes = codes.iter().cloned();
+  while let Some(code) = codes.next() {
+    style = match code {
+      0 => Style::default(),
+      1 => style.add_modifier(Modifier::BOLD),
+      2 => style.add_modifier(Modifier::DIM),
+      3 => style.add_modifier(Modifier::ITALIC),
+      4 => style.add_modifier(Modifier::UNDERLINED),
+      5 => style.add_modifier(Modifier::SLOW_BLINK),
+      7 => style.add_modifier(Modifier::REVERSED),
+      9 => style.add_modifier(Modifier::CROSSED_OUT),
+      22 => style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+      23 => style.remove_modifier(Modifier::ITALIC),
+      24 => style.remove_modifier(Modifier::UNDERLINED),
+      25 => style.remove_modifier(Modifier::SLOW_BLINK),
+      27 => style.remove_modifier(Modifier::REVERSED),
+      29 => style.remove_modifier(Modifier::CROSSED_OUT),
+      30..=37 => style.fg(basic_color(code - 30)),
+      38 => match extended_color(&mut codes) {
+        Some(color) => style.fg(color),
+        None => style,
+      },
+      39 => Style { fg: None, ..style },
+      40..=47 => style.bg(basic_color(code - 40)),
+      48 => match extended_color(&mut codes) {
+        Some(color) => style.bg(color),
+        None => style,
+      },
+      49 => Style { bg: None, ..style },
+      90..=97 => style.fg(bright_color(code - 90)),
+      100..=107 => style.bg(bright_color(code - 100)),
+      _ => style,
+    };
+  }
+
+  style
+}
+
+// `5;n` for one of 256 colours, or `2;r;g;b`
+fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Option<Color> {
+  match codes.next()? {
+    5 => Some(Color::Indexed(codes.next()?)),
+    2 => Some(Color::Rgb(codes.next()?, codes.next()?, codes.next()?)),
+    _ => None,
+  }
+}
+
+fn basic_color(n: u8) -> Color {
+  match n {
+    0 => Color::Black,
+    1 => Color::Red,
+    2 => Color::Green,
+    3 => Color::Yellow,
+    4 => Color::Blue,
+    5 => Color::Magenta,
+    6 => Color::Cyan,
+    _ => Color::Gray,
+  }
+}
+
+fn bright_color(n: u8) -> Color {
+  match n {
+    0 => Color::DarkGray,
+    1 => Color::LightRed,
+    2 => Color::LightGreen,
+    3 => Color::LightYellow,
+    4 => Color::LightBlue,
+    5 => Color::LightMagenta,
+    6 => Color::LightCyan,
+    _ => Color::White,
+  }
+}
+
+pub fn plain(spans: &[Span]) -> String {
+  spans.iter().map(|span| span.content.as_ref()).collect()
+}
+
+pub fn is_styled(spans: &[Span]) -> bool {
+  spans.iter().any(|span| span.style != Style::default())
+}
+
+// the spans covering the given byte range of the plain text
+pub fn slice(spans: &[Span<'static>], range: Range<usize>) -> Spans<'static> {
+  let mut result = vec![];
+  let mut start = 0;
+  for span in spans {
+    let end = start + span.content.len();
+    let overlap = range.start.max(start)..range.end.min(end);
+    if overlap.start < overlap.end {
+      result.push(Span::styled(
+        span.content[overlap.start - start..overlap.end - start].to_owned(),
+        span.style,
+      ));
+    }
+    start = end;
+  }
+
+  Spans::from(result)
+}
+
+pub fn to_text(text: &str) -> Text<'static> {
+  Text::from(
+    parse_lines(text)
+      .into_iter()
+      .map(Spans::from)
+      .collect::<Vec<Spans>>(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_parse_lines() {
+    let text = "\x1b[01;34mdocs\x1b[0m  plain \x1b[1;38;5;208morange\x1b[39m bold";
+
+    assert_eq!(
+      parse_lines(text),
+      vec![vec![
+        Span::styled(
+          "docs",
+          Style::default()
+            .fg(Color::Blue)
+            .add_modifier(Modifier::BOLD)
+        ),
+        Span::raw("  plain "),
+        Span::styled(
+          "orange",
+          Style::default()
+            .fg(Color::Indexed(208))
+            .add_modifier(Modifier::BOLD)
+        ),
+        Span::styled(" bold", Style::default().add_modifier(Modifier::BOLD)),
+      ]]
+    );
+  }
+
+  #[test]
+  fn test_colours_carry_over_lines() {
+    let lines = parse_lines("\x1b[32mone\ntwo\x1b[m\nthree");
+
+    assert_eq!(
+      lines,
+      vec![
+        vec![Span::styled("one", Style::default().fg(Color::Green))],
+        vec![Span::styled("two", Style::default().fg(Color::Green))],
+        vec![Span::raw("three")],
+      ]
+    );
+  }
+
+  #[test]
+  fn test_drops_other_escape_codes() {
+    let text = "\x1b]8;;file:///tmp/a.txt\x1b\\a.txt\x1b]8;;\x1b\\ \x1b[Kb\x1b[2Jc";
+
+    assert_eq!(plain(&parse_lines(text)[0]), "a.txt bc");
+  }
+
+  #[test]
+  fn test_slice() {
+    let spans = parse_lines("ab\x1b[31mcd\x1b[0mef").remove(0);
+
+    assert_eq!(
+      slice(&spans, 1..5),
+      Spans::from(vec![
+        Span::raw("b"),
+        Span::styled("cd", Style::default().fg(Color::Red)),
+        Span::raw("e"),
+      ])
+    );
+  }
+}
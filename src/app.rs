@@ -1,14 +1,95 @@
-use std::path::PathBuf;
+use crossterm::event::KeyEvent;
+use regex::Regex;
+use std::{
+  cmp,
+  collections::{HashMap, HashSet},
+  env,
+  path::PathBuf,
+  time::Duration,
+};
+use unicode_width::UnicodeWidthStr;
 
 use crate::{
   args::Args,
-  command,
-  config::{Config, Profile},
-  parse::Row,
+  command::CommandLine,
+  config::{self, ColumnRef, Config, Format, KeyBinding, Multi, Output, Profile, RowKey},
+  jobs::Jobs,
+  output_view::OutputView,
+  parse::{self, ParseOptions, Parsed, Row},
+  preview_cache::PreviewCache,
   stateful_table::StatefulTable,
+  stream_buffer::StreamBuffer,
   template,
 };
 
+const PREVIEW_CACHE_CAPACITY: usize = 50;
+
+// for display commands without a timeout of their own
+const DEFAULT_DISPLAY_COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Returns a function giving the key that identifies a row across refetches. We
+// build the function up-front so that we only compile the regex once per refetch.
+type RowKeyExtractor = Box<dyn Fn(&Row) -> Option<String>>;
+
+fn row_key_extractor(row_key: Option<&RowKey>, column_names: &[String]) -> RowKeyExtractor {
+  match row_key {
+    None => Box::new(|row| Some(row.original_line.clone())),
+    Some(RowKey::Column(column)) => {
+      let index = match column {
+        ColumnRef::Index(index) => Some(*index),
+        ColumnRef::Name(name) => column_names.iter().position(|c| c == name),
+      };
+      Box::new(move |row| row.cells.get(index?).cloned())
+    }
+    Some(RowKey::Regex(regex)) => match Regex::new(regex) {
+      Ok(regex) => Box::new(move |row| {
+        let captures = regex.captures(&row.original_line)?;
+        let key = captures.get(1).or_else(|| captures.get(0))?;
+        Some(key.as_str().to_owned())
+      }),
+      // a malformed regex means we can't identify rows, in which case the cursor stays put
+      Err(_) => Box::new(|_| None),
+    },
+  }
+}
+
+// A row's original line (in tree mode, following the lines of its ancestors),
+// along with how many identical ones come before it. Unlike an index this
+// survives refetches, and unlike the line alone it tells apart rows that read
+// the same.
+type RowId = (String, usize);
+
+// `seen` counts the identical lines so far, carrying over from the rows before
+// when rows are appended
+fn row_ids(rows: &[Row], tree: bool, seen: &mut HashMap<String, usize>) -> Vec<RowId> {
+  // the depth and line of each ancestor of the current row, in tree mode
+  let mut ancestors: Vec<(usize, &str)> = vec![];
+  rows
+    .iter()
+    .map(|row| {
+      let path = if tree {
+        while ancestors
+          .last()
+          .is_some_and(|(depth, _)| *depth >= row.depth)
+        {
+          ancestors.pop();
+        }
+        ancestors.push((row.depth, &row.original_line));
+        ancestors
+          .iter()
+          .map(|(_, line)| *line)
+          .collect::<Vec<&str>>()
+          .join("\n")
+      } else {
+        row.original_line.clone()
+      };
+      let count = seen.entry(path.clone()).or_insert(0);
+      *count += 1;
+      (path, *count - 1)
+    })
+    .collect()
+}
+
 #[derive(PartialEq)]
 pub enum FocusedPanel {
   Table,
@@ -16,110 +97,1065 @@ pub enum FocusedPanel {
   // TODO: should I store the error here in the enum, given
   // it isn't needed anywhere else, and only applies to that panel?
   ErrorPopup(String),
-  ConfirmationPopup(String),
+  ConfirmationPopup(CommandBatch),
+  OutputPopup(OutputView),
+  Jobs,
+  // the output of a job picked from the jobs panel, which we go back to on close
+  JobOutputPopup(OutputView),
+}
+
+// the commands resolved from a single keypress, along with how to run them
+#[derive(PartialEq, Clone)]
+pub struct CommandBatch {
+  pub commands: Vec<CommandLine>,
+  pub interactive: bool,
+  pub output: Output,
+  pub timeout: Option<Duration>,
 }
 
 pub struct App<'a> {
   pub rows: Vec<Row>,
+  // the id of each row, see RowId
+  row_ids: Vec<RowId>,
+  // how many times each line appears in `rows`, for giving appended rows their ids
+  line_counts: HashMap<String, usize>,
+  // each row's original line, lowercased for filtering
+  lowercase_lines: Vec<String>,
+  // indices into `rows` of the rows matching `filter_text`
+  filtered_indices: Vec<usize>,
+  // the filter text `filtered_indices` was worked out for
+  applied_filter_text: String,
+  // the widest cell in each column among the filtered rows
+  pub column_widths: Vec<usize>,
+  pub column_names: Vec<String>,
+  // where each column starts, so that streamed lines can be split the same way
+  column_indices: Vec<usize>,
+  // the column showing how rows nest, in tree mode
+  pub tree_column: Option<usize>,
+  // the rows whose children are hidden
+  collapsed_rows: HashSet<RowId>,
   pub table: StatefulTable,
   pub config: &'a Config,
   pub profile: Option<&'a Profile>,
   pub args: Args,
   pub status_text: Option<String>,
+  // the last line printed by a keybinding whose output goes to the status bar, or
+  // a note that the streamed command has exited
+  pub command_result: Option<String>,
+  pub fetch_in_flight: bool,
+  // set when we need to fetch again as soon as the in-flight fetch finishes
+  pub refetch_queued: bool,
+  // background refreshes we've skipped because the previous fetch hadn't finished
+  pub skipped_fetches: usize,
   pub filter_text: String,
+  // rows are identified by id so that marks survive refetches and filtering
+  pub marked_rows: HashSet<RowId>,
   pub focused_panel: FocusedPanel,
-  pub selected_item_content: String,
+  // the display command for the selected row, whose output we want to show
+  pub preview_command: Option<CommandLine>,
+  // Err if the display command failed, in which case we show its stderr
+  pub selected_item_content: Result<String, String>,
+  pub preview_cache: PreviewCache,
+  // the commands run by keybindings, past and present
+  pub jobs: Jobs,
+  // the lines read so far, when streaming
+  pub stream_buffer: Option<StreamBuffer>,
   pub config_path: PathBuf,
 }
 
 impl<'a> App<'a> {
   // TODO: do we really need a reference to the config? We should probably move it in here. But then we need to still work out how to have a profile field. We could either make that a function or make it an immutable reference
   pub fn new(config: &'a Config, config_path: PathBuf, args: Args) -> App<'a> {
-    let profile = config.find_profile_for_command(args.command.as_ref());
+    let profile = match (&args.profile, &args.command) {
+      (Some(name), _) => config.find_profile_by_name(name),
+      (None, Some(command)) => config.find_profile_for_command(command),
+      (None, None) => None,
+    };
 
     App {
       table: StatefulTable::new(0),
       rows: vec![],
+      row_ids: vec![],
+      line_counts: HashMap::new(),
+      lowercase_lines: vec![],
+      filtered_indices: vec![],
+      applied_filter_text: String::from(""),
+      column_widths: vec![],
+      column_names: vec![],
+      column_indices: vec![],
+      tree_column: None,
+      collapsed_rows: HashSet::new(),
       config,
       profile,
       args,
       status_text: None,
+      command_result: None,
+      fetch_in_flight: false,
+      refetch_queued: false,
+      skipped_fetches: 0,
       filter_text: String::from(""),
+      marked_rows: HashSet::new(),
       focused_panel: FocusedPanel::Table,
-      selected_item_content: String::from(""),
+      preview_command: None,
+      selected_item_content: Ok(String::from("")),
+      preview_cache: PreviewCache::new(PREVIEW_CACHE_CAPACITY),
+      jobs: Jobs::new(
+        profile
+          .and_then(|profile| profile.max_concurrent_jobs)
+          .unwrap_or(1),
+      ),
+      stream_buffer: None,
       config_path,
     }
   }
 
-  pub fn on_select(&mut self) -> Option<()> {
+  // Returns the display command we need to run in the background for the newly
+  // selected row, if we don't already have (or aren't already waiting on) its output.
+  pub fn on_select(&mut self) -> Option<CommandLine> {
     let selected_row = self.get_selected_row()?;
-    let command_template = self.profile?.display_command.as_ref()?;
-    let command = template::resolve_command(command_template, selected_row);
+    let profile = self.profile?;
+    let command_template = match profile.display_command.as_ref() {
+      Some(command_template) => command_template,
+      None => {
+        // without a display command, we show the whole of the selected record
+        if profile.record_separator.is_some() {
+          self.selected_item_content = Ok(selected_row.original_line.clone());
+        }
+        return None;
+      }
+    };
+    let command =
+      match template::resolve_command(command_template, selected_row, &self.column_names) {
+        Ok(command) => command,
+        Err(error) => {
+          self.preview_command = None;
+          self.selected_item_content = Err(error);
+          return None;
+        }
+      };
 
-    let output = command::run_command(&command).unwrap();
-    self.selected_item_content = output;
+    if self.preview_command.as_ref() == Some(&command) {
+      return None;
+    }
+    self.preview_command = Some(command.clone());
 
-    Some(())
+    match self.preview_cache.get(&command.to_string()) {
+      Some(output) => {
+        self.selected_item_content = Ok(output);
+        None
+      }
+      None => {
+        self.selected_item_content = Ok(String::from("Loading..."));
+        Some(command)
+      }
+    }
+  }
+
+  // the command line to run scripts with, if it's been configured
+  pub fn shell(&self) -> Option<Vec<String>> {
+    let shell = self
+      .profile
+      .and_then(|profile| profile.shell.as_ref())
+      .or(self.config.shell.as_ref())?;
+
+    Some(shell.argv(env::var("SHELL").ok()))
+  }
+
+  pub fn command_timeout(&self) -> Option<Duration> {
+    self.profile?.timeout.and_then(config::timeout_duration)
+  }
+
+  pub fn display_command_timeout(&self) -> Option<Duration> {
+    let display_command = self
+      .profile
+      .and_then(|profile| profile.display_command.as_ref());
+    match display_command.and_then(|display_command| display_command.timeout) {
+      Some(seconds) => config::timeout_duration(seconds),
+      None => Some(DEFAULT_DISPLAY_COMMAND_TIMEOUT),
+    }
+  }
+
+  pub fn has_preview(&self) -> bool {
+    matches!(self.profile, Some(profile) if profile.display_command.is_some()
+      || profile.record_separator.is_some()
+      || profile.key_bindings.iter().any(|kb| kb.output == Output::Preview))
+  }
+
+  pub fn on_preview_loaded(&mut self, command: CommandLine, result: Result<String, String>) {
+    // by the time the output arrives the user may have moved on to another row
+    if self.preview_command.as_ref() == Some(&command) {
+      self.selected_item_content = result.clone();
+    }
+
+    match result {
+      Ok(output) => self.preview_cache.insert(command.to_string(), output),
+      Err(_) => self.preview_cache.remove(&command.to_string()),
+    }
+  }
+
+  // for when we've run a command that may have changed what the previews would show
+  pub fn invalidate_previews(&mut self) {
+    self.preview_cache.clear();
+    self.preview_command = None;
   }
 
   pub fn filtered_rows(&self) -> Vec<&Row> {
+    self
+      .filtered_indices
+      .iter()
+      .map(|index| &self.rows[*index])
+      .collect()
+  }
+
+  pub fn filtered_row(&self, index: usize) -> Option<&Row> {
+    self
+      .filtered_indices
+      .get(index)
+      .map(|index| &self.rows[*index])
+  }
+
+  pub fn filtered_row_count(&self) -> usize {
+    self.filtered_indices.len()
+  }
+
+  // Works out which rows match the filter text. Typing another character can only
+  // narrow things down, so in that case we only look at the rows that matched before.
+  // Without filter text, we show the rows that aren't folded away; with it, we
+  // show every match, folded or not.
+  fn refilter(&mut self) {
     let lc_filter_text = self.filter_text.to_ascii_lowercase();
+    let candidates = if !self.applied_filter_text.is_empty()
+      && lc_filter_text.starts_with(&self.applied_filter_text)
+    {
+      std::mem::take(&mut self.filtered_indices)
+    } else {
+      (0..self.rows.len()).collect()
+    };
 
-    match self.filter_text.as_ref() {
-      // TODO: ask if this is idiomatic rust: i.e. converting a Vec<Row> to Vec<&Row>
-      "" => self.rows.iter().collect(),
-      _ => self
-        .rows
-        .iter()
-        .filter(|row| {
-          row
-            .original_line
-            .to_ascii_lowercase()
-            .contains(&lc_filter_text)
-        })
-        .collect(),
+    let lowercase_lines = &self.lowercase_lines;
+    self.filtered_indices = if lc_filter_text.is_empty() {
+      self.unfolded_indices()
+    } else {
+      candidates
+        .into_iter()
+        .filter(|index| lowercase_lines[*index].contains(&lc_filter_text))
+        .collect()
+    };
+    self.applied_filter_text = lc_filter_text;
+
+    self.column_widths = self.get_column_widths();
+    self.adjust_cursor();
+  }
+
+  fn unfolded_indices(&self) -> Vec<usize> {
+    if self.collapsed_rows.is_empty() {
+      return (0..self.rows.len()).collect();
+    }
+
+    let mut result = vec![];
+    // the depth of the collapsed row we're below, if any
+    let mut folded_below = None;
+    for (index, row) in self.rows.iter().enumerate() {
+      match folded_below {
+        Some(depth) if row.depth > depth => continue,
+        _ => folded_below = None,
+      }
+      if self.collapsed_rows.contains(&self.row_ids[index]) {
+        folded_below = Some(row.depth);
+      }
+      result.push(index);
+    }
+
+    result
+  }
+
+  fn has_children(&self, row_index: usize) -> bool {
+    match self.rows.get(row_index + 1) {
+      Some(next) => next.depth > self.rows[row_index].depth,
+      None => false,
+    }
+  }
+
+  // what to show before the value in the tree column: indentation, and whether
+  // the row is expanded or collapsed
+  pub fn tree_prefix(&self, index: usize) -> String {
+    let row_index = self.filtered_indices[index];
+    let row = &self.rows[row_index];
+    let chevron = if !self.has_children(row_index) {
+      " "
+    } else if self.collapsed_rows.contains(&self.row_ids[row_index]) {
+      "▸"
+    } else {
+      "▾"
+    };
+
+    format!("{}{} ", "  ".repeat(row.depth), chevron)
+  }
+
+  // folds the selected row's children away, or if there are none to fold, moves
+  // the cursor to its parent
+  pub fn collapse_selected(&mut self) {
+    let selected = self.table.state.selected().unwrap_or(0);
+    let row_index = match self.filtered_indices.get(selected) {
+      Some(row_index) if self.tree_column.is_some() => *row_index,
+      _ => return,
+    };
+
+    if self.has_children(row_index) && self.collapsed_rows.insert(self.row_ids[row_index].clone()) {
+      self.refilter();
+      return;
+    }
+
+    let depth = self.rows[row_index].depth;
+    let parent = (0..selected)
+      .rev()
+      .find(|index| self.rows[self.filtered_indices[*index]].depth < depth);
+    if let Some(parent) = parent {
+      self.table.state.select(Some(parent));
+    }
+  }
+
+  pub fn expand_selected(&mut self) {
+    let selected = self.table.state.selected().unwrap_or(0);
+    let row_index = match self.filtered_indices.get(selected) {
+      Some(row_index) if self.tree_column.is_some() => *row_index,
+      _ => return,
+    };
+
+    if self.collapsed_rows.remove(&self.row_ids[row_index]) {
+      self.refilter();
+    }
+  }
+
+  fn get_column_widths(&self) -> Vec<usize> {
+    let mut widths = self
+      .column_names
+      .iter()
+      .map(|name| name.width())
+      .collect::<Vec<usize>>();
+
+    for row in self.filtered_rows() {
+      self.widen_columns(&mut widths, row);
+    }
+
+    widths
+  }
+
+  fn widen_columns(&self, widths: &mut Vec<usize>, row: &Row) {
+    // unselectable rows are just context and get clipped to fit
+    if row.unselectable {
+      return;
+    }
+
+    if widths.len() < row.cells.len() && self.column_names.is_empty() {
+      widths.resize(row.cells.len(), 0);
+    }
+    for (column, (width, cell)) in widths.iter_mut().zip(row.cells.iter()).enumerate() {
+      let tree_prefix_width = match self.tree_column {
+        // see tree_prefix
+        Some(tree_column) if tree_column == column => row.depth * 2 + 2,
+        _ => 0,
+      };
+      *width = cmp::max(*width, tree_prefix_width + cell.width());
     }
   }
 
   pub fn get_selected_row(&self) -> Option<&Row> {
     let selected_index = self.table.state.selected().unwrap();
 
-    Some(*self.filtered_rows().get(selected_index)?)
+    let row = self.filtered_row(selected_index)?;
+    if row.unselectable {
+      return None;
+    }
+
+    Some(row)
+  }
+
+  pub fn is_marked(&self, index: usize) -> bool {
+    self
+      .marked_rows
+      .contains(&self.row_ids[self.filtered_indices[index]])
+      || matches!(self.table.visual_range(), Some(range) if range.contains(&index))
+  }
+
+  pub fn has_marks(&self) -> bool {
+    !self.marked_rows.is_empty() || self.table.visual_anchor.is_some()
+  }
+
+  // how many rows are marked, including the visual range, without going through
+  // every row like target_rows does. Marked rows that are filtered out count too.
+  pub fn mark_count(&self) -> usize {
+    let in_range = match self.table.visual_range() {
+      Some(range) => self
+        .filtered_indices
+        .get(range)
+        .unwrap_or_default()
+        .iter()
+        .filter(|row_index| {
+          !self.rows[**row_index].unselectable
+            && !self.marked_rows.contains(&self.row_ids[**row_index])
+        })
+        .count(),
+      None => 0,
+    };
+
+    self.marked_rows.len() + in_range
+  }
+
+  // the rows that a keybinding applies to: the marked rows if there are any,
+  // otherwise the selected row
+  pub fn target_rows(&self) -> Vec<&Row> {
+    if !self.has_marks() {
+      return self.get_selected_row().into_iter().collect();
+    }
+
+    self
+      .filtered_rows()
+      .into_iter()
+      .enumerate()
+      .filter(|(index, row)| !row.unselectable && self.is_marked(*index))
+      .map(|(_index, row)| row)
+      .collect()
+  }
+
+  pub fn key_binding(&self, event: &KeyEvent) -> Option<&'a KeyBinding> {
+    self
+      .profile?
+      .key_bindings
+      .iter()
+      .find(|kb| kb.key.matches(event))
+  }
+
+  pub fn resolve_commands(&self, binding: &KeyBinding) -> Result<Vec<CommandLine>, String> {
+    let rows = self.target_rows();
+    if rows.is_empty() {
+      return Ok(vec![]);
+    }
+
+    match binding.multi {
+      Multi::Each => template::resolve_command_for_each_row(binding, &rows, &self.column_names),
+      Multi::Join => {
+        template::resolve_command_for_rows(binding, &rows, &self.column_names).map(|c| vec![c])
+      }
+    }
+  }
+
+  pub fn toggle_mark(&mut self) -> Option<()> {
+    self.get_selected_row()?;
+    let id = self.row_ids[self.filtered_indices[self.table.state.selected()?]].clone();
+
+    if !self.marked_rows.remove(&id) {
+      self.marked_rows.insert(id);
+    }
+
+    Some(())
+  }
+
+  // leaving visual mode keeps the rows in the range marked
+  pub fn toggle_visual_mode(&mut self) {
+    match self.table.visual_range() {
+      Some(range) => {
+        let ids = self
+          .filtered_indices
+          .get(range)
+          .unwrap_or_default()
+          .iter()
+          .map(|row_index| self.row_ids[*row_index].clone())
+          .collect::<Vec<RowId>>();
+
+        self.marked_rows.extend(ids);
+        self.table.visual_anchor = None;
+      }
+      None => self.table.visual_anchor = self.table.state.selected(),
+    }
+  }
+
+  pub fn clear_marks(&mut self) {
+    self.marked_rows.clear();
+    self.table.visual_anchor = None;
   }
 
   pub fn adjust_cursor(&mut self) {
-    let filtered_rows = self.filtered_rows();
-    let length = filtered_rows.len();
+    let length = self.filtered_row_count();
+    let unselectable = self
+      .filtered_rows()
+      .iter()
+      .enumerate()
+      .filter(|(_index, row)| row.unselectable)
+      .map(|(index, _row)| index)
+      .collect();
     self.table.row_count = length;
+    self.table.unselectable = unselectable;
     // if our cursor is too far we need to correct it
     if length == 0 {
       self.table.state.select(Some(0));
     } else if self.table.state.selected().unwrap() > length - 1 {
       self.table.state.select(Some(length - 1));
     }
+    self.table.avoid_unselectable();
   }
 
-  pub fn update_rows(&mut self, rows: Vec<Row>) {
-    self.rows = rows;
+  pub fn update_rows(&mut self, parsed: Parsed) {
+    let row_key = self.profile.and_then(|profile| profile.row_key.as_ref());
+    let selected_key = self
+      .get_selected_row()
+      .and_then(|row| row_key_extractor(row_key, &self.column_names)(row));
+
+    self.rows = parsed.rows;
+    self.line_counts.clear();
+    self.column_names = parsed.column_names;
+    self.column_indices = parsed.column_indices;
+    self.tree_column = parsed.tree_column;
+    self.row_ids = row_ids(
+      &self.rows,
+      self.tree_column.is_some(),
+      &mut self.line_counts,
+    );
+    self.lowercase_lines = self
+      .rows
+      .iter()
+      .map(|row| row.original_line.to_ascii_lowercase())
+      .collect();
+
+    if !self.marked_rows.is_empty() || !self.collapsed_rows.is_empty() {
+      let ids = self.row_ids.iter().collect::<HashSet<&RowId>>();
+      self.marked_rows.retain(|id| ids.contains(id));
+      self.collapsed_rows.retain(|id| ids.contains(id));
+    }
+
+    self.applied_filter_text = String::from("");
+    self.refilter();
+
+    // keep the cursor on the same item if it's still there, otherwise stay where we are
+    if let Some(selected_key) = selected_key {
+      let extract_key = row_key_extractor(row_key, &self.column_names);
+      let new_index = self
+        .filtered_rows()
+        .iter()
+        .position(|row| extract_key(row).as_ref() == Some(&selected_key));
+
+      if let Some(new_index) = new_index {
+        self.table.state.select(Some(new_index));
+      }
+    }
+
     self.adjust_cursor();
   }
 
+  // While the cursor is on the last row, new rows from a stream pull it along
+  // (like `tail -f`). Anywhere else, it stays pinned to the row it's on.
+  pub fn is_following(&self) -> bool {
+    self.table.state.selected().unwrap_or(0) + 1 >= self.filtered_row_count()
+  }
+
+  pub fn on_lines_streamed(&mut self, lines: Vec<String>) -> Result<(), String> {
+    let following = self.is_following();
+    let options = self.parse_options();
+    let max_rows = self
+      .args
+      .max_rows
+      .or_else(|| self.profile.and_then(|profile| profile.max_rows));
+    let buffer = self.stream_buffer.get_or_insert_with(|| {
+      let head_len = options.lines_to_skip
+        + if options.header && options.format == Format::Table {
+          1
+        } else {
+          0
+        };
+      StreamBuffer::new(head_len, max_rows)
+    });
+
+    // Once we've got rows to go on, new lines are parsed on their own, unless
+    // they'd push old lines out, in which case there are at most `max_rows` lines
+    // to reparse.
+    let appended = if !self.rows.is_empty() && buffer.appends_cleanly(lines.len()) {
+      parse::parse_more(&lines, &options, &self.column_indices)?
+    } else {
+      None
+    };
+    buffer.extend(lines);
+
+    match appended {
+      Some(rows) => self.append_rows(rows),
+      None => {
+        let parsed = parse::parse(buffer.text(), &options)?;
+        self.update_rows(parsed);
+      }
+    }
+    if following {
+      self
+        .table
+        .state
+        .select(Some(self.table.row_count.saturating_sub(1)));
+    }
+
+    Ok(())
+  }
+
+  // Adds rows streamed in after the ones we've got. Unlike update_rows, this only
+  // looks at the new rows, so that a long stream doesn't slow down as it grows.
+  fn append_rows(&mut self, rows: Vec<Row>) {
+    let start = self.rows.len();
+    self.lowercase_lines.extend(
+      rows
+        .iter()
+        .map(|row| row.original_line.to_ascii_lowercase()),
+    );
+    self.row_ids.extend(row_ids(
+      &rows,
+      self.tree_column.is_some(),
+      &mut self.line_counts,
+    ));
+    self.rows.extend(rows);
+
+    let mut widths = std::mem::take(&mut self.column_widths);
+    for index in start..self.rows.len() {
+      if !self.lowercase_lines[index].contains(&self.applied_filter_text) {
+        continue;
+      }
+      if self.rows[index].unselectable {
+        self.table.unselectable.insert(self.filtered_indices.len());
+      }
+      self.filtered_indices.push(index);
+      self.widen_columns(&mut widths, &self.rows[index]);
+    }
+    self.column_widths = widths;
+
+    self.table.row_count = self.filtered_indices.len();
+    self.table.avoid_unselectable();
+  }
+
+  pub fn is_streaming(&self) -> bool {
+    self.args.stream || matches!(self.profile, Some(profile) if profile.stream)
+  }
+
+  // piped input can only be read once, and a streaming command is never done
+  pub fn can_refetch(&self) -> bool {
+    self.args.command.is_some() && !self.is_streaming()
+  }
+
+  // command line arguments take precedence over the profile
+  pub fn parse_options(&self) -> ParseOptions {
+    let lines_to_skip = if self.args.lines_to_skip != 0 {
+      self.args.lines_to_skip
+    } else {
+      match self.profile {
+        Some(profile) => profile.lines_to_skip,
+        None => 0,
+      }
+    };
+
+    let format = match (self.args.format, self.profile) {
+      (Some(format), _) => format,
+      (None, Some(profile)) => profile.format,
+      (None, None) => Format::default(),
+    };
+
+    let delimiter = match (&self.args.delimiter, self.profile) {
+      (Some(delimiter), _) => Some(delimiter.clone()),
+      (None, Some(profile)) => profile.delimiter.clone(),
+      (None, None) => None,
+    };
+
+    let columns = match self.profile {
+      Some(profile) => profile.columns.clone(),
+      None => vec![],
+    };
+
+    let header = match self.profile {
+      Some(profile) => profile.header,
+      None => false,
+    };
+
+    let (row_regex, show_unmatched_lines) = match self.profile {
+      Some(profile) => (profile.row_regex.clone(), profile.show_unmatched_lines),
+      None => (None, false),
+    };
+
+    let record_separator = self
+      .profile
+      .and_then(|profile| profile.record_separator.clone());
+
+    let tree = matches!(self.profile, Some(profile) if profile.tree);
+
+    let (skip_trailing_lines, ignore_lines_matching, unselectable_lines_matching) =
+      match self.profile {
+        Some(profile) => (
+          profile.skip_trailing_lines,
+          profile.ignore_lines_matching.clone(),
+          profile.unselectable_lines_matching.clone(),
+        ),
+        None => (0, vec![], vec![]),
+      };
+
+    ParseOptions {
+      lines_to_skip,
+      skip_trailing_lines,
+      ignore_lines_matching,
+      unselectable_lines_matching,
+      format,
+      delimiter,
+      header,
+      columns,
+      row_regex,
+      show_unmatched_lines,
+      record_separator,
+      tree,
+      column_indices: vec![],
+    }
+  }
+
   pub fn on_tick(&mut self) {
     // do nothing for now
   }
 
   pub fn push_filter_text_char(&mut self, c: char) {
     self.filter_text.push(c);
-    self.adjust_cursor();
+    self.refilter();
   }
 
   pub fn pop_filter_text_char(&mut self) {
     self.filter_text.pop();
-    self.adjust_cursor();
+    self.refilter();
   }
 
   pub fn reset_filter_text(&mut self) {
     self.filter_text = String::from("");
-    self.adjust_cursor();
+    self.refilter();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::{DisplayCommand, RecordSeparator};
+  use pretty_assertions::assert_eq;
+
+  fn docker_row(status: &str) -> Row {
+    Row::new(
+      format!("17c523089229   blah   {}", status),
+      vec![
+        String::from("17c523089229"),
+        String::from("blah"),
+        String::from(status),
+      ],
+    )
+  }
+
+  fn args(command: &str) -> Args {
+    Args {
+      command: Some(String::from(command)),
+      profile: None,
+      lines_to_skip: 0,
+      refresh_frequency: 0.0,
+      format: None,
+      delimiter: None,
+      stream: false,
+      max_rows: None,
+    }
+  }
+
+  // a config with just the one profile, and args that pick it
+  fn profile_fixture(profile: Profile) -> (Config, Args) {
+    let args = Args {
+      profile: Some(profile.name.clone()),
+      ..args(&profile.name)
+    };
+    let config = Config {
+      shell: None,
+      profiles: vec![profile],
+    };
+
+    (config, args)
+  }
+
+  fn streaming_args() -> Args {
+    Args {
+      stream: true,
+      ..args("tail -f log.txt")
+    }
+  }
+
+  fn lines(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|line| String::from(*line)).collect()
+  }
+
+  #[test]
+  fn test_streaming_follows_tail() {
+    let config = Config::new();
+    let mut app = App::new(&config, PathBuf::new(), streaming_args());
+    app.table.next();
+
+    app.on_lines_streamed(lines(&["a", "b"])).unwrap();
+    assert_eq!(app.get_selected_row().unwrap().original_line, "b");
+
+    app.on_lines_streamed(lines(&["c"])).unwrap();
+    assert_eq!(app.get_selected_row().unwrap().original_line, "c");
+  }
+
+  #[test]
+  fn test_streaming_pins_cursor_away_from_tail() {
+    let config = Config::new();
+    let mut app = App::new(&config, PathBuf::new(), streaming_args());
+    app.table.next();
+
+    app.on_lines_streamed(lines(&["a", "b"])).unwrap();
+    app.table.previous();
+    app.on_lines_streamed(lines(&["c"])).unwrap();
+
+    assert_eq!(app.get_selected_row().unwrap().original_line, "a");
+  }
+
+  #[test]
+  fn test_marking_one_of_several_identical_rows() {
+    let config = Config::new();
+    let mut app = App::new(&config, PathBuf::new(), args("ls"));
+    app.table.next();
+    let row = |line: &str| Row::new(String::from(line), vec![String::from(line)]);
+    app.update_rows(Parsed {
+      rows: vec![row("a"), row("a"), row("b")],
+      ..Default::default()
+    });
+
+    app.toggle_mark();
+    assert!(app.is_marked(0));
+    assert!(!app.is_marked(1));
+    assert_eq!(app.mark_count(), 1);
+
+    let binding = KeyBinding {
+      command: config::CommandTemplate::from("echo $0"),
+      ..Default::default()
+    };
+    assert_eq!(
+      app.resolve_commands(&binding),
+      Ok(vec![CommandLine::from("echo a")])
+    );
+
+    // the mark stays on the first of the two after a refetch
+    app.update_rows(Parsed {
+      rows: vec![row("b"), row("a"), row("a")],
+      ..Default::default()
+    });
+    assert_eq!(
+      (0..3)
+        .map(|index| app.is_marked(index))
+        .collect::<Vec<bool>>(),
+      vec![false, true, false]
+    );
+
+    // a marked row in the visual range is only counted once
+    app.table.state.select(Some(0));
+    app.toggle_visual_mode();
+    app.table.state.select(Some(2));
+    assert_eq!(app.mark_count(), 3);
+  }
+
+  #[test]
+  fn test_streaming_reworks_columns_when_a_line_does_not_fit() {
+    let config = Config::new();
+    let mut app = App::new(&config, PathBuf::new(), streaming_args());
+    app.table.next();
+    let cells =
+      |app: &App| -> Vec<Vec<String>> { app.rows.iter().map(|row| row.cells.clone()).collect() };
+
+    app.on_lines_streamed(lines(&["a   1", "b   2"])).unwrap();
+    app.on_lines_streamed(lines(&["c   3"])).unwrap();
+    assert_eq!(
+      cells(&app),
+      vec![lines(&["a", "1"]), lines(&["b", "2"]), lines(&["c", "3"])]
+    );
+
+    // the columns are worked out again, as if everything had been read at once
+    app.on_lines_streamed(lines(&["dddddd 4"])).unwrap();
+    let parsed = parse::parse(
+      String::from("a   1\nb   2\nc   3\ndddddd 4"),
+      &ParseOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(app.rows, parsed.rows);
+    assert_eq!(app.filtered_row_count(), 4);
+  }
+
+  #[test]
+  fn test_row_key_defaults_to_whole_line() {
+    let extract_key = row_key_extractor(None, &[]);
+
+    assert_eq!(
+      extract_key(&docker_row("Up 43 seconds")),
+      Some(String::from("17c523089229   blah   Up 43 seconds"))
+    );
+  }
+
+  #[test]
+  fn test_row_key_by_column() {
+    let column_names = vec![
+      String::from("CONTAINER ID"),
+      String::from("NAMES"),
+      String::from("STATUS"),
+    ];
+
+    for row_key in [
+      RowKey::Column(ColumnRef::Index(1)),
+      RowKey::Column(ColumnRef::Name(String::from("NAMES"))),
+    ] {
+      let extract_key = row_key_extractor(Some(&row_key), &column_names);
+      assert_eq!(
+        extract_key(&docker_row("Up 43 seconds")),
+        extract_key(&docker_row("Up 44 seconds"))
+      );
+      assert_eq!(
+        extract_key(&docker_row("Up 43 seconds")),
+        Some(String::from("blah"))
+      );
+    }
+  }
+
+  #[test]
+  fn test_row_key_by_regex() {
+    let with_group = RowKey::Regex(String::from(r"^(\w+)\s"));
+    let without_group = RowKey::Regex(String::from(r"^\w+"));
+
+    for row_key in [with_group, without_group] {
+      let extract_key = row_key_extractor(Some(&row_key), &[]);
+      assert_eq!(
+        extract_key(&docker_row("Up 43 seconds")),
+        Some(String::from("17c523089229"))
+      );
+    }
+  }
+
+  #[test]
+  fn test_preview_shows_whole_record() {
+    let (config, args) = profile_fixture(Profile {
+      name: String::from("ip addr"),
+      record_separator: Some(RecordSeparator::Indentation),
+      ..Default::default()
+    });
+    let mut app = App::new(&config, PathBuf::new(), args);
+    app.table.next();
+    let parsed = parse::parse(
+      String::from("1: lo: <LOOPBACK,UP>\n    inet 127.0.0.1/8\n2: eth0: <BROADCAST>"),
+      &app.parse_options(),
+    )
+    .unwrap();
+    app.update_rows(parsed);
+
+    assert!(app.has_preview());
+    assert_eq!(app.on_select(), None);
+    assert_eq!(
+      app.selected_item_content,
+      Ok(String::from("1: lo: <LOOPBACK,UP>\n    inet 127.0.0.1/8"))
+    );
+  }
+
+  #[test]
+  fn test_failed_previews_are_not_cached() {
+    let (config, args) = profile_fixture(Profile {
+      name: String::from("ls"),
+      display_command: Some(DisplayCommand {
+        command: config::CommandTemplate::from("cat $0"),
+        regex: None,
+        timeout: None,
+      }),
+      ..Default::default()
+    });
+    let mut app = App::new(&config, PathBuf::new(), args);
+    app.table.next();
+    let row = |line: &str| Row::new(String::from(line), vec![String::from(line)]);
+    app.update_rows(Parsed {
+      rows: vec![row("a"), row("b")],
+      ..Default::default()
+    });
+
+    let command = app.on_select().unwrap();
+    app.on_preview_loaded(command.clone(), Err(String::from("timed out")));
+    app.table.next();
+    let other_command = app.on_select().unwrap();
+    app.on_preview_loaded(other_command, Ok(String::from("b's contents")));
+
+    // coming back runs the failed command again, but not the one that succeeded
+    app.table.previous();
+    assert_eq!(app.on_select(), Some(command));
+    app.table.next();
+    assert_eq!(app.on_select(), None);
+    assert_eq!(app.selected_item_content, Ok(String::from("b's contents")));
+  }
+
+  #[test]
+  fn test_folding_tree() {
+    let (config, args) = profile_fixture(Profile {
+      name: String::from("tree"),
+      tree: true,
+      ..Default::default()
+    });
+    let mut app = App::new(&config, PathBuf::new(), args);
+    app.table.next();
+    let parsed = parse::parse(
+      String::from(".\n├── src\n│   ├── main.rs\n│   └── app.rs\n└── README.md"),
+      &app.parse_options(),
+    )
+    .unwrap();
+    app.update_rows(parsed);
+    let visible = |app: &App| {
+      app
+        .filtered_rows()
+        .iter()
+        .map(|row| row.cells[0].clone())
+        .collect::<Vec<String>>()
+    };
+
+    app.table.next();
+    assert_eq!(app.tree_prefix(1), "  ▾ ");
+    app.collapse_selected();
+    assert_eq!(visible(&app), vec![".", "src", "README.md"]);
+    assert_eq!(app.tree_prefix(1), "  ▸ ");
+
+    // searching looks inside folded rows
+    app.push_filter_text_char('m');
+    assert_eq!(visible(&app), vec!["main.rs", "README.md"]);
+    app.reset_filter_text();
+
+    app.expand_selected();
+    assert_eq!(
+      visible(&app),
+      vec![".", "src", "main.rs", "app.rs", "README.md"]
+    );
+
+    // with nothing to fold, we move to the parent instead
+    app.table.next();
+    app.collapse_selected();
+    assert_eq!(app.get_selected_row().unwrap().cells[0], "src");
+  }
+
+  #[test]
+  fn test_folding_one_of_several_identical_rows() {
+    let (config, args) = profile_fixture(Profile {
+      name: String::from("tree"),
+      tree: true,
+      ..Default::default()
+    });
+    let mut app = App::new(&config, PathBuf::new(), args);
+    app.table.next();
+    // both `lib`s are `│   ├── lib`
+    let parsed = parse::parse(
+      String::from(
+        ".\n├── a\n│   ├── lib\n│   │   └── x.rs\n│   └── c\n├── b\n│   ├── lib\n│   │   └── y.rs\n│   └── d\n└── e",
+      ),
+      &app.parse_options(),
+    )
+    .unwrap();
+    app.update_rows(parsed);
+
+    app.table.state.select(Some(2));
+    app.collapse_selected();
+    let visible = app
+      .filtered_rows()
+      .iter()
+      .map(|row| row.cells[0].clone())
+      .collect::<Vec<String>>();
+    assert_eq!(
+      visible,
+      vec![".", "a", "lib", "c", "b", "lib", "y.rs", "d", "e"]
+    );
   }
 }
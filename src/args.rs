@@ -1,12 +1,23 @@
 use clap::{App as ClapApp, Arg};
+use crossterm::tty::IsTty;
+
+use crate::config::{Delimiter, Format};
 
 pub struct Args {
-  pub command: String,
+  // None when we're reading the output of a pipe, e.g. `docker ps | lazycli`
+  pub command: Option<String>,
+  pub profile: Option<String>,
   pub lines_to_skip: usize,
   pub refresh_frequency: f64,
+  pub format: Option<Format>,
+  pub delimiter: Option<Delimiter>,
+  pub stream: bool,
+  pub max_rows: Option<usize>,
 }
 
 impl Args {
+  // reads the command line, so there's no default to speak of
+  #[allow(clippy::new_without_default)]
   pub fn new() -> Args {
     let matches = ClapApp::new("lazycli")
       .version("0.1")
@@ -28,13 +39,54 @@ impl Args {
           .about("frequency of refreshing the content in seconds (values like 0.1 are permitted. Values like 0.001? Also permitted, but you need to seriously look yourself in the eyes and ask whether that's something you want. Be careful, if you stare into your own eyes long enough in the mirror, a moment eventually comes when you realise that you truly exist and are aware that you exist. A revelation you might not want to inflict on yourself, especially if it's just for the sake of knowing deep down whether you want to push the limits of a command line argument)")
           .takes_value(true),
       )
+      .arg(
+        Arg::new("format")
+          .short('f')
+          .long("format")
+          .value_name("FORMAT")
+          .about("how to parse the output of the command: `table` (the default) for whitespace-aligned columns, or `json` for a JSON array or one JSON object per line")
+          .possible_values(&["table", "json"])
+          .takes_value(true),
+      )
+      .arg(
+        Arg::new("delimiter")
+          .short('d')
+          .long("delimiter")
+          .value_name("DELIMITER")
+          .about("split lines into columns on this rather than on whitespace: `csv`, `tsv`, text like `:` or `||`, or a regex like `regex:\\s*\\|\\s*`")
+          .takes_value(true),
+      )
+      .arg(
+        Arg::new("profile")
+          .short('p')
+          .long("profile")
+          .value_name("PROFILE")
+          .about("the name of the profile to use, rather than finding one that matches the command. Handy when piping output into lazycli")
+          .takes_value(true),
+      )
+      .arg(
+        Arg::new("stream")
+          .short('s')
+          .long("stream")
+          .about("for commands that keep running, like `tail -f`: add rows as lines arrive rather than waiting for the command to exit"),
+      )
+      .arg(
+        Arg::new("max-rows")
+          .long("max-rows")
+          .value_name("MAX_ROWS")
+          .about("when streaming, only keep the latest `n` rows")
+          .takes_value(true),
+      )
       .arg(Arg::new("command").multiple(true))
       .get_matches();
 
     let command = match matches.values_of("command") {
-      Some(matches) => matches.collect::<Vec<&str>>().join(" "),
+      Some(matches) => Some(matches.collect::<Vec<&str>>().join(" ")),
+      None if !std::io::stdin().is_tty() => None,
       None => {
-        eprintln!("Usage: Command must be supplied, e.g.: `lazycli -- ls -l`");
+        eprintln!(
+          "Usage: Command must be supplied, e.g.: `lazycli -- ls -l`, or piped in, e.g.: `ls -l | lazycli`"
+        );
         std::process::exit(1);
       }
     };
@@ -61,10 +113,45 @@ impl Args {
       },
     };
 
+    let format = match matches.value_of("format") {
+      None => None,
+      Some("json") => Some(Format::Json),
+      Some(_) => Some(Format::Table),
+    };
+
+    let delimiter = match matches.value_of("delimiter") {
+      None => None,
+      Some(s) => match Delimiter::parse(s) {
+        Ok(delimiter) => Some(delimiter),
+        Err(error) => {
+          eprintln!("{}", error);
+          std::process::exit(1);
+        }
+      },
+    };
+
+    let profile = matches.value_of("profile").map(String::from);
+
+    let max_rows = match matches.value_of("max-rows") {
+      None => None,
+      Some(s) => match s.parse::<usize>() {
+        Ok(n) => Some(n),
+        Err(_) => {
+          eprintln!("max-rows argument must be a number");
+          std::process::exit(1);
+        }
+      },
+    };
+
     Args {
       command,
+      profile,
       lines_to_skip,
       refresh_frequency,
+      format,
+      delimiter,
+      stream: matches.is_present("stream"),
+      max_rows,
     }
   }
 }
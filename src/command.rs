@@ -1,30 +1,584 @@
-use std::process::Command;
+use crossterm::tty::IsTty;
+use std::{
+  fmt,
+  fs::File,
+  io::{self, Read},
+  process::{Child, ChildStdout, Command, Stdio},
+  sync::{
+    mpsc::{self, RecvTimeoutError, Sender},
+    Mutex, OnceLock,
+  },
+  thread::{self, JoinHandle},
+  time::Duration,
+};
 
-pub fn run_command(command: &str) -> Result<String, String> {
-  let output = Command::new("bash")
-    .args(&["-c", command])
+use crate::{config::DEFAULT_SHELL, template::shell_escape};
+
+// A command ready to run, with its placeholders filled in
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandLine {
+  // a script for the shell, e.g. `docker ps | grep api`
+  Shell(String),
+  // a program and its arguments, run without a shell
+  Exec(Vec<String>),
+}
+
+impl From<&str> for CommandLine {
+  fn from(script: &str) -> CommandLine {
+    CommandLine::Shell(String::from(script))
+  }
+}
+
+// how the command is shown to the user
+impl fmt::Display for CommandLine {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      CommandLine::Shell(script) => write!(f, "{}", script),
+      CommandLine::Exec(argv) => write!(
+        f,
+        "{}",
+        argv
+          .iter()
+          .map(|arg| shell_escape(arg))
+          .collect::<Vec<String>>()
+          .join(" ")
+      ),
+    }
+  }
+}
+
+// the command line scripts are run with, where `{}` stands for the script
+static SHELL: OnceLock<Vec<String>> = OnceLock::new();
+
+// called once at startup, before any commands are run
+pub fn set_shell(argv: Vec<String>) {
+  SHELL.set(argv).ok();
+}
+
+fn process(command: &CommandLine) -> Command {
+  match command {
+    CommandLine::Shell(script) => {
+      let default_shell = || {
+        vec![
+          String::from(DEFAULT_SHELL),
+          String::from("-c"),
+          String::from("{}"),
+        ]
+      };
+      shell_process(SHELL.get().cloned().unwrap_or_else(default_shell), script)
+    }
+    CommandLine::Exec(argv) => {
+      let mut process = Command::new(argv.first().map(String::as_str).unwrap_or(""));
+      process.args(argv.iter().skip(1));
+      process
+    }
+  }
+}
+
+// If no argument has a `{}` in it, the script goes on the end.
+fn shell_process(shell: Vec<String>, script: &str) -> Command {
+  let mut args = shell
+    .iter()
+    .skip(1)
+    .map(|arg| arg.replace("{}", script))
+    .collect::<Vec<String>>();
+  if !shell.iter().skip(1).any(|arg| arg.contains("{}")) {
+    args.push(script.to_owned());
+  }
+
+  let mut process = Command::new(shell.first().map(String::as_str).unwrap_or(DEFAULT_SHELL));
+  process.args(args);
+  process
+}
+
+fn spawn_error(command: &CommandLine, error: io::Error) -> String {
+  let program = match command {
+    CommandLine::Shell(_) => SHELL
+      .get()
+      .and_then(|shell| shell.first().cloned())
+      .unwrap_or_else(|| String::from(DEFAULT_SHELL)),
+    CommandLine::Exec(argv) => argv.first().cloned().unwrap_or_default(),
+  };
+
+  match (command, error.kind()) {
+    (CommandLine::Shell(_), io::ErrorKind::NotFound) => format!(
+      "Failed to run command `{}`: the shell `{}` wasn't found. You can set `shell` in your config to use a different one.",
+      command, program
+    ),
+    _ => format!("Failed to run command `{}` ({}): {}", command, program, error),
+  }
+}
+
+// how long a cancelled command gets to clean up after SIGINT before we SIGKILL it
+const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+// Every command we've spawned and not yet waited on, so that they can be
+// cancelled. Each one leads its own process group, so signalling the group
+// reaches everything it started, like the other end of a pipe.
+static RUNNING: Mutex<Vec<RunningCommand>> = Mutex::new(Vec::new());
+
+struct RunningCommand {
+  pid: u32,
+  // the job the command belongs to, if it was run by a keybinding
+  job: Option<usize>,
+  cancelled: bool,
+  timed_out: bool,
+}
+
+fn spawn_tracked(job: Option<usize>, process: &mut Command) -> io::Result<Child> {
+  #[cfg(unix)]
+  std::os::unix::process::CommandExt::process_group(process, 0);
+
+  let child = process.spawn()?;
+  RUNNING.lock().unwrap().push(RunningCommand {
+    pid: child.id(),
+    job,
+    cancelled: false,
+    timed_out: false,
+  });
+
+  Ok(child)
+}
+
+// called once the child has been waited on
+fn untrack(pid: u32) -> Option<RunningCommand> {
+  let mut running = RUNNING.lock().unwrap();
+  let index = running.iter().position(|command| command.pid == pid)?;
+  Some(running.remove(index))
+}
+
+// Kills the command if it's still running once the timeout is up. Dropping the
+// returned sender calls off the watch.
+fn watch(pid: u32, timeout: Duration) -> Sender<()> {
+  let (done_tx, done_rx) = mpsc::channel::<()>();
+
+  thread::spawn(move || {
+    if done_rx.recv_timeout(timeout) == Err(RecvTimeoutError::Timeout) {
+      if let Some(command) = RUNNING
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .find(|command| command.pid == pid)
+      {
+        command.timed_out = true;
+        kill(pid);
+      }
+    }
+  });
+
+  done_tx
+}
+
+// whether there's anything left for `cancel_running` to cancel
+pub fn is_running() -> bool {
+  RUNNING
+    .lock()
+    .unwrap()
+    .iter()
+    .any(|command| !command.cancelled)
+}
+
+// Interrupts every running command, killing any that are still around once the
+// grace period is up. Returns how many commands were cancelled.
+pub fn cancel_running() -> usize {
+  cancel_where(|_| true)
+}
+
+pub fn cancel_job(job: usize) -> usize {
+  cancel_where(|command| command.job == Some(job))
+}
+
+fn cancel_where<F: Fn(&RunningCommand) -> bool>(condition: F) -> usize {
+  let mut pids = vec![];
+  for command in RUNNING.lock().unwrap().iter_mut() {
+    if !command.cancelled && condition(command) {
+      command.cancelled = true;
+      pids.push(command.pid);
+    }
+  }
+
+  for pid in pids.iter() {
+    interrupt(*pid);
+  }
+
+  if !pids.is_empty() {
+    let pids = pids.clone();
+    thread::spawn(move || {
+      thread::sleep(CANCEL_GRACE_PERIOD);
+      // anything we've since waited on is gone from the list, so its PID can't
+      // have been reused by some unrelated process
+      for command in RUNNING.lock().unwrap().iter() {
+        if pids.contains(&command.pid) {
+          kill(command.pid);
+        }
+      }
+    });
+  }
+
+  pids.len()
+}
+
+// for when we're quitting: nothing we started should outlive us
+pub fn kill_running() {
+  for command in RUNNING.lock().unwrap().iter() {
+    kill(command.pid);
+  }
+}
+
+#[cfg(unix)]
+fn interrupt(pid: u32) {
+  signal_group(pid, libc::SIGINT);
+}
+
+#[cfg(unix)]
+fn kill(pid: u32) {
+  signal_group(pid, libc::SIGKILL);
+}
+
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: libc::c_int) {
+  // a negative PID addresses the whole process group
+  unsafe {
+    libc::kill(-(pid as libc::pid_t), signal);
+  }
+}
+
+// Windows has no SIGINT to send, so cancelling is killing
+#[cfg(not(unix))]
+fn interrupt(pid: u32) {
+  kill(pid);
+}
+
+#[cfg(not(unix))]
+fn kill(pid: u32) {
+  Command::new("taskkill")
+    .args(["/T", "/F", "/PID", &pid.to_string()])
     .output()
-    .expect(&format!("failed to run command {}", command));
+    .ok();
+}
+
+pub fn run_command(command: &CommandLine, timeout: Option<Duration>) -> Result<String, String> {
+  let output = run_command_with_output(command, timeout);
 
-  if !output.status.success() {
-    return Err(String::from_utf8(output.stderr).unwrap());
+  if !output.success() {
+    return Err(output.error());
   }
 
-  Ok(String::from_utf8(output.stdout).unwrap())
+  Ok(output.stdout)
+}
+
+// everything a command left behind, for when we want to show it to the user
+#[derive(Debug, PartialEq, Clone)]
+pub struct CommandOutput {
+  pub command: String,
+  pub stdout: String,
+  pub stderr: String,
+  // None if the command was killed by a signal
+  pub exit_code: Option<i32>,
+  pub cancelled: bool,
+  // set if the command was killed for taking longer than this
+  pub timed_out_after: Option<Duration>,
+}
+
+impl CommandOutput {
+  pub fn success(&self) -> bool {
+    self.exit_code == Some(0) && !self.cancelled && self.timed_out_after.is_none()
+  }
+
+  // what to tell the user when the command didn't succeed
+  pub fn error(&self) -> String {
+    if self.cancelled {
+      format!("Command `{}` was cancelled", self.command)
+    } else if let Some(timeout) = self.timed_out_after {
+      format!("Command `{}` timed out after {:?}", self.command, timeout)
+    } else {
+      self.stderr.clone()
+    }
+  }
+}
+
+pub fn run_command_with_output(command: &CommandLine, timeout: Option<Duration>) -> CommandOutput {
+  run_tracked_command(command, None, timeout)
+}
+
+pub fn run_job_command(
+  command: &CommandLine,
+  job: usize,
+  timeout: Option<Duration>,
+) -> CommandOutput {
+  run_tracked_command(command, Some(job), timeout)
+}
+
+fn run_tracked_command(
+  command: &CommandLine,
+  job: Option<usize>,
+  timeout: Option<Duration>,
+) -> CommandOutput {
+  let failed = |error: io::Error| CommandOutput {
+    command: command.to_string(),
+    stdout: String::from(""),
+    stderr: spawn_error(command, error),
+    exit_code: None,
+    cancelled: false,
+    timed_out_after: None,
+  };
+
+  // commands run in the background don't get to read from the terminal
+  let child = match spawn_tracked(
+    job,
+    process(command)
+      .stdin(Stdio::null())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped()),
+  ) {
+    Ok(child) => child,
+    Err(error) => return failed(error),
+  };
+  let pid = child.id();
+  let watchdog = timeout.map(|timeout| watch(pid, timeout));
+  let output = child.wait_with_output();
+  drop(watchdog);
+  let tracked = untrack(pid);
+  let output = match output {
+    Ok(output) => output,
+    Err(error) => return failed(error),
+  };
+
+  // output isn't guaranteed to be valid UTF-8 (e.g. `git show` on a binary file) and
+  // that shouldn't bring the whole app down
+  CommandOutput {
+    command: command.to_string(),
+    stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+    stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    exit_code: output.status.code(),
+    cancelled: tracked.as_ref().is_some_and(|command| command.cancelled),
+    timed_out_after: timeout.filter(|_| tracked.is_some_and(|command| command.timed_out)),
+  }
+}
+
+// the child inherits our stdin/stdout/stderr so that it can take over the terminal
+pub fn run_interactive_command(command: &CommandLine) -> Result<(), String> {
+  let mut child = process(command);
+
+  // if our stdin is a pipe, the child needs the terminal's input instead
+  if !io::stdin().is_tty() {
+    if let Ok(tty) = File::open("/dev/tty") {
+      child.stdin(tty);
+    }
+  }
+
+  let status = child
+    .status()
+    .map_err(|error| spawn_error(command, error))?;
+
+  if !status.success() {
+    return Err(format!("Command `{}` exited with {}", command, status));
+  }
+
+  Ok(())
+}
+
+// A command whose output we read as it arrives, for commands that may never exit.
+pub struct StreamingCommand {
+  command: String,
+  child: Child,
+  // stderr is drained as we go so that a chatty command can't fill the pipe and block
+  stderr: JoinHandle<String>,
+}
+
+impl StreamingCommand {
+  pub fn spawn(command: &CommandLine) -> Result<(StreamingCommand, ChildStdout), String> {
+    let mut child = spawn_tracked(
+      None,
+      process(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped()),
+    )
+    .map_err(|error| spawn_error(command, error))?;
+
+    let stdout = child.stdout.take().unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    let stderr = thread::spawn(move || {
+      let mut output = vec![];
+      stderr.read_to_end(&mut output).ok();
+      String::from_utf8_lossy(&output).into_owned()
+    });
+
+    Ok((
+      StreamingCommand {
+        command: command.to_string(),
+        child,
+        stderr,
+      },
+      stdout,
+    ))
+  }
+
+  pub fn wait(mut self) -> Result<(), String> {
+    let status = self.child.wait();
+    if untrack(self.child.id()).is_some_and(|command| command.cancelled) {
+      return Err(format!("Command `{}` was cancelled", self.command));
+    }
+    let status = status.map_err(|error| error.to_string())?;
+    let stderr = self.stderr.join().unwrap_or_default();
+
+    if !status.success() {
+      return Err(stderr);
+    }
+
+    Ok(())
+  }
 }
 
 #[cfg(test)]
 #[test]
 fn test_run_command() {
-  let result = run_command("echo 1");
+  let result = run_command(&CommandLine::from("echo 1"), None);
   assert_eq!(result, Ok(String::from("1\n")));
 }
 
 #[test]
 fn test_run_command_fail() {
-  let result = run_command("asldfkjh test");
+  let result = run_command(&CommandLine::from("asldfkjh test"), None);
   assert!(matches!(
     result,
     Err(e) if e.contains("command not found") && e.contains("asldfkjh"),
   ));
 }
+
+#[test]
+fn test_exec_without_shell() {
+  let command = CommandLine::Exec(vec![
+    String::from("echo"),
+    String::from("$HOME; rm -rf /"),
+    String::from("it's"),
+  ]);
+
+  assert_eq!(
+    run_command(&command, None),
+    Ok(String::from("$HOME; rm -rf / it's\n"))
+  );
+  assert_eq!(command.to_string(), "echo '$HOME; rm -rf /' 'it'\\''s'");
+}
+
+#[test]
+fn test_missing_program() {
+  let command = CommandLine::Exec(vec![String::from("asldfkjh")]);
+  let output = run_command_with_output(&command, None);
+
+  assert!(!output.success());
+  assert!(output
+    .error()
+    .starts_with("Failed to run command `asldfkjh` (asldfkjh)"));
+}
+
+#[test]
+fn test_shell_process() {
+  let args = |process: Command| {
+    process
+      .get_args()
+      .map(|arg| arg.to_string_lossy().into_owned())
+      .collect::<Vec<String>>()
+  };
+
+  let shell = vec![String::from("sh"), String::from("-c"), String::from("{}")];
+  assert_eq!(
+    args(shell_process(shell, "ls | wc -l")),
+    vec!["-c", "ls | wc -l"]
+  );
+
+  let shell = vec![String::from("fish"), String::from("--command")];
+  assert_eq!(args(shell_process(shell, "ls")), vec!["--command", "ls"]);
+}
+
+#[test]
+fn test_run_command_with_output() {
+  let output = run_command_with_output(&CommandLine::from("echo done; echo careful >&2"), None);
+
+  assert!(output.success());
+  assert_eq!(output.stdout, "done\n");
+  assert_eq!(output.stderr, "careful\n");
+
+  assert_eq!(
+    run_command_with_output(&CommandLine::from("exit 3"), None).exit_code,
+    Some(3)
+  );
+}
+
+#[test]
+fn test_run_interactive_command_fail() {
+  let result = run_interactive_command(&CommandLine::from("exit 3"));
+  assert!(matches!(
+    result,
+    Err(e) if e.contains("exit 3") && e.contains("status: 3"),
+  ));
+}
+
+#[test]
+fn test_streaming_command() {
+  let (command, mut stdout) =
+    StreamingCommand::spawn(&CommandLine::from("echo 1; echo 2")).unwrap();
+  let mut output = String::new();
+  stdout.read_to_string(&mut output).unwrap();
+
+  assert_eq!(output, "1\n2\n");
+  assert_eq!(command.wait(), Ok(()));
+}
+
+#[test]
+fn test_streaming_command_fail() {
+  let (command, _stdout) =
+    StreamingCommand::spawn(&CommandLine::from("echo oops >&2; exit 1")).unwrap();
+
+  assert_eq!(command.wait(), Err(String::from("oops\n")));
+}
+
+#[test]
+fn test_timeout() {
+  let timeout = Duration::from_millis(200);
+  let started = std::time::Instant::now();
+  let result = run_command(&CommandLine::from("sleep 30 | sleep 31"), Some(timeout));
+
+  assert!(started.elapsed() < Duration::from_secs(5));
+  assert_eq!(
+    result,
+    Err(String::from(
+      "Command `sleep 30 | sleep 31` timed out after 200ms"
+    ))
+  );
+  assert_eq!(
+    run_command(&CommandLine::from("echo quick"), Some(timeout)),
+    Ok(String::from("quick\n"))
+  );
+}
+
+#[test]
+fn test_cancel_pipeline() {
+  // the `sleep`s hold on to stdout, so we'd be stuck waiting on them if they
+  // survived bash being interrupted
+  let job = 4242;
+  let handle =
+    thread::spawn(move || run_job_command(&CommandLine::from("sleep 30 | sleep 31"), job, None));
+  while !RUNNING
+    .lock()
+    .unwrap()
+    .iter()
+    .any(|running| running.job == Some(job))
+  {
+    thread::sleep(Duration::from_millis(10));
+  }
+  // give bash a moment to start the pipeline
+  thread::sleep(Duration::from_millis(200));
+
+  let started = std::time::Instant::now();
+  assert_eq!(cancel_job(job), 1);
+  let output = handle.join().unwrap();
+
+  assert!(started.elapsed() < CANCEL_GRACE_PERIOD);
+  assert!(output.cancelled);
+  assert_eq!(
+    output.error(),
+    String::from("Command `sleep 30 | sleep 31` was cancelled")
+  );
+}
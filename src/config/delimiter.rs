@@ -0,0 +1,127 @@
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+// How to split a line into cells, for output that isn't aligned with whitespace.
+// Written in the config as `csv`, `tsv`, a regex after `regex:` like
+// `regex:\s*\|\s*`, or otherwise the text to split on, like `:` or `||`.
+// Any of those can be given as text by putting `text:` in front, e.g. `text:csv`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Delimiter {
+  // comma-separated, with double quotes around values that contain commas, quotes or newlines
+  Csv,
+  // tab-separated, without any quoting
+  Tsv,
+  Char(char),
+  Text(String),
+  Regex(String),
+}
+
+impl Delimiter {
+  pub fn parse(spec: &str) -> Result<Delimiter, String> {
+    if let Some(regex) = spec.strip_prefix("regex:") {
+      return match Regex::new(regex) {
+        // splitting on a regex that matches nothing would split between every character
+        Ok(compiled) if compiled.is_match("") => Err(format!(
+          "Delimiter regex `{}` can't match an empty string",
+          regex
+        )),
+        Ok(_) => Ok(Delimiter::Regex(regex.to_owned())),
+        Err(error) => Err(format!("Invalid delimiter regex `{}`: {}", regex, error)),
+      };
+    }
+
+    match spec {
+      "csv" => Ok(Delimiter::Csv),
+      "tsv" => Ok(Delimiter::Tsv),
+      _ => Delimiter::text(spec.strip_prefix("text:").unwrap_or(spec)),
+    }
+  }
+
+  fn text(text: &str) -> Result<Delimiter, String> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+      (None, _) => Err(String::from("Delimiter can't be empty")),
+      (Some(c), None) => Ok(Delimiter::Char(c)),
+      _ => Ok(Delimiter::Text(text.to_owned())),
+    }
+  }
+}
+
+impl fmt::Display for Delimiter {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Delimiter::Csv => write!(f, "csv"),
+      Delimiter::Tsv => write!(f, "tsv"),
+      Delimiter::Char(c) => write!(f, "{}", c),
+      Delimiter::Text(text) => match Delimiter::parse(text) {
+        Ok(Delimiter::Text(_)) => write!(f, "{}", text),
+        // it would be read back as something else
+        _ => write!(f, "text:{}", text),
+      },
+      Delimiter::Regex(regex) => write!(f, "regex:{}", regex),
+    }
+  }
+}
+
+impl Serialize for Delimiter {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&self.to_string())
+  }
+}
+
+impl<'de> Deserialize<'de> for Delimiter {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Delimiter, D::Error> {
+    let spec = String::deserialize(deserializer)?;
+    Delimiter::parse(&spec).map_err(de::Error::custom)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_parse() {
+    let cases = [
+      ("csv", Delimiter::Csv),
+      ("tsv", Delimiter::Tsv),
+      (":", Delimiter::Char(':')),
+      ("\t", Delimiter::Char('\t')),
+      ("||", Delimiter::Text(String::from("||"))),
+      (" | ", Delimiter::Text(String::from(" | "))),
+      ("text:csv", Delimiter::Text(String::from("csv"))),
+      (
+        r"regex:\s*\|\s*",
+        Delimiter::Regex(String::from(r"\s*\|\s*")),
+      ),
+    ];
+
+    for (spec, expected) in cases {
+      assert_eq!(
+        Delimiter::parse(spec),
+        Ok(expected.clone()),
+        "parsing `{}`",
+        spec
+      );
+      assert_eq!(Delimiter::parse(&expected.to_string()), Ok(expected));
+    }
+  }
+
+  #[test]
+  fn test_parse_invalid() {
+    assert!(Delimiter::parse("").is_err());
+    assert!(Delimiter::parse("text:").is_err());
+    assert!(matches!(
+      Delimiter::parse("regex:(unclosed"),
+      Err(e) if e.starts_with("Invalid delimiter regex `(unclosed`"),
+    ));
+    assert_eq!(
+      Delimiter::parse("regex:||"),
+      Err(String::from(
+        "Delimiter regex `||` can't match an empty string"
+      ))
+    );
+  }
+}
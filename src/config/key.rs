@@ -0,0 +1,229 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+// A key as written in the config, e.g. `d`, `D`, `enter`, `f5`, `ctrl+d` or
+// `alt+shift+k`. Modifiers come first, separated from the key by `+`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Key {
+  pub code: KeyCode,
+  pub modifiers: KeyModifiers,
+}
+
+static NAMED_KEYS: &[(&str, KeyCode)] = &[
+  ("enter", KeyCode::Enter),
+  ("tab", KeyCode::Tab),
+  ("backtab", KeyCode::BackTab),
+  ("backspace", KeyCode::Backspace),
+  ("delete", KeyCode::Delete),
+  ("insert", KeyCode::Insert),
+  ("esc", KeyCode::Esc),
+  ("space", KeyCode::Char(' ')),
+  ("left", KeyCode::Left),
+  ("right", KeyCode::Right),
+  ("up", KeyCode::Up),
+  ("down", KeyCode::Down),
+  ("home", KeyCode::Home),
+  ("end", KeyCode::End),
+  ("pageup", KeyCode::PageUp),
+  ("pagedown", KeyCode::PageDown),
+];
+
+impl Key {
+  pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Key {
+    // terminals don't agree on whether to report shift for characters, given
+    // that the character's case already tells us, so we fold it into the character.
+    match code {
+      KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => Key {
+        code: KeyCode::Char(c.to_ascii_uppercase()),
+        modifiers: modifiers - KeyModifiers::SHIFT,
+      },
+      KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => Key {
+        code: KeyCode::BackTab,
+        modifiers: modifiers - KeyModifiers::SHIFT,
+      },
+      KeyCode::BackTab => Key {
+        code,
+        modifiers: modifiers - KeyModifiers::SHIFT,
+      },
+      _ => Key { code, modifiers },
+    }
+  }
+
+  pub fn matches(&self, event: &KeyEvent) -> bool {
+    *self == Key::new(event.code, event.modifiers)
+  }
+
+  pub fn parse(spec: &str) -> Result<Key, String> {
+    let mut parts = spec.split('+').collect::<Vec<&str>>();
+    // `ctrl++` ends in an empty part, which is the plus key itself
+    let key_part = match parts.pop() {
+      Some("") if spec.ends_with('+') => {
+        parts.pop();
+        "+"
+      }
+      Some(part) => part,
+      None => "",
+    };
+
+    let mut modifiers = KeyModifiers::NONE;
+    for part in parts {
+      modifiers |= match part.to_ascii_lowercase().as_ref() {
+        "ctrl" | "control" => KeyModifiers::CONTROL,
+        "alt" => KeyModifiers::ALT,
+        "shift" => KeyModifiers::SHIFT,
+        _ => return Err(format!("Unknown modifier `{}` in key `{}`", part, spec)),
+      };
+    }
+
+    let code = parse_code(key_part).ok_or_else(|| format!("Unknown key `{}`", spec))?;
+
+    Ok(Key::new(code, modifiers))
+  }
+}
+
+fn parse_code(s: &str) -> Option<KeyCode> {
+  let mut chars = s.chars();
+  if let (Some(c), None) = (chars.next(), chars.next()) {
+    return Some(KeyCode::Char(c));
+  }
+
+  let lowercase = s.to_ascii_lowercase();
+
+  if let Some(&(_, code)) = NAMED_KEYS.iter().find(|(name, _)| *name == lowercase) {
+    return Some(code);
+  }
+
+  let n = lowercase.strip_prefix('f')?.parse::<u8>().ok()?;
+  if (1..=12).contains(&n) {
+    Some(KeyCode::F(n))
+  } else {
+    None
+  }
+}
+
+impl From<char> for Key {
+  fn from(c: char) -> Key {
+    Key::new(KeyCode::Char(c), KeyModifiers::NONE)
+  }
+}
+
+impl fmt::Display for Key {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if self.modifiers.contains(KeyModifiers::CONTROL) {
+      write!(f, "ctrl+")?;
+    }
+    if self.modifiers.contains(KeyModifiers::ALT) {
+      write!(f, "alt+")?;
+    }
+    if self.modifiers.contains(KeyModifiers::SHIFT) {
+      write!(f, "shift+")?;
+    }
+
+    match self.code {
+      KeyCode::Char(' ') => write!(f, "space"),
+      KeyCode::Char(c) => write!(f, "{}", c),
+      KeyCode::F(n) => write!(f, "f{}", n),
+      code => match NAMED_KEYS
+        .iter()
+        .find(|(_, named_code)| *named_code == code)
+      {
+        Some((name, _)) => write!(f, "{}", name),
+        None => write!(f, "{:?}", code),
+      },
+    }
+  }
+}
+
+impl Serialize for Key {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&self.to_string())
+  }
+}
+
+impl<'de> Deserialize<'de> for Key {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Key, D::Error> {
+    let spec = String::deserialize(deserializer)?;
+    Key::parse(&spec).map_err(de::Error::custom)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_parse() {
+    let cases = vec![
+      ("d", Key::new(KeyCode::Char('d'), KeyModifiers::NONE)),
+      ("D", Key::new(KeyCode::Char('D'), KeyModifiers::NONE)),
+      ("+", Key::new(KeyCode::Char('+'), KeyModifiers::NONE)),
+      ("enter", Key::new(KeyCode::Enter, KeyModifiers::NONE)),
+      ("Tab", Key::new(KeyCode::Tab, KeyModifiers::NONE)),
+      ("f5", Key::new(KeyCode::F(5), KeyModifiers::NONE)),
+      (
+        "ctrl+d",
+        Key::new(KeyCode::Char('d'), KeyModifiers::CONTROL),
+      ),
+      (
+        "ctrl++",
+        Key::new(KeyCode::Char('+'), KeyModifiers::CONTROL),
+      ),
+      (
+        "alt+shift+k",
+        Key::new(KeyCode::Char('K'), KeyModifiers::ALT),
+      ),
+      ("shift+tab", Key::new(KeyCode::BackTab, KeyModifiers::NONE)),
+    ];
+
+    for (spec, expected) in cases {
+      assert_eq!(Key::parse(spec), Ok(expected), "parsing `{}`", spec);
+    }
+  }
+
+  #[test]
+  fn test_parse_invalid() {
+    assert_eq!(Key::parse("f13"), Err(String::from("Unknown key `f13`")));
+    assert_eq!(
+      Key::parse("hyper+d"),
+      Err(String::from("Unknown modifier `hyper` in key `hyper+d`"))
+    );
+  }
+
+  #[test]
+  fn test_display_round_trip() {
+    for spec in [
+      "d", "D", "space", "enter", "f5", "ctrl+d", "alt+K", "backtab",
+    ] {
+      assert_eq!(Key::parse(spec).unwrap().to_string(), spec);
+    }
+  }
+
+  #[test]
+  fn test_yaml_round_trip() {
+    // plain characters are what older configs will contain
+    for yaml in ["d", "ctrl+d", "f5"] {
+      let key: Key = serde_yaml::from_str(yaml).unwrap();
+      let serialized = serde_yaml::to_string(&key).unwrap();
+      assert_eq!(serialized.trim_start_matches("---").trim(), yaml);
+    }
+  }
+
+  #[test]
+  fn test_matches_regardless_of_reported_shift() {
+    let key = Key::parse("K").unwrap();
+
+    assert!(key.matches(&KeyEvent::new(KeyCode::Char('K'), KeyModifiers::SHIFT)));
+    assert!(key.matches(&KeyEvent::new(KeyCode::Char('K'), KeyModifiers::NONE)));
+    assert!(!key.matches(&KeyEvent::new(KeyCode::Char('k'), KeyModifiers::NONE)));
+  }
+
+  #[test]
+  fn test_matches_modifiers() {
+    let key = Key::parse("ctrl+d").unwrap();
+
+    assert!(key.matches(&KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL)));
+    assert!(!key.matches(&KeyEvent::new(KeyCode::Char('d'), KeyModifiers::NONE)));
+  }
+}
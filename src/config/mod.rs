@@ -1,11 +1,21 @@
+mod delimiter;
+mod key;
 mod profile_matching;
 pub mod storage;
 
+pub use delimiter::Delimiter;
+pub use key::Key;
+
 use profile_matching::command_matches;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Config {
+  // the shell that commands are run with, unless a profile says otherwise
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub shell: Option<Shell>,
   pub profiles: Vec<Profile>,
 }
 
@@ -37,62 +47,319 @@ pub struct Profile {
   #[serde(default = "usize::default")]
   #[serde(skip_serializing_if = "IsZero::is_zero")]
   pub lines_to_skip: usize,
+  // the number of lines at the end of the output to leave out, e.g. a totals line
+  #[serde(default = "usize::default")]
+  #[serde(skip_serializing_if = "IsZero::is_zero")]
+  pub skip_trailing_lines: usize,
+  // regexes for lines to leave out wherever they appear, e.g. `^total \d+` for `ls -l`
+  #[serde(default = "Vec::default")]
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub ignore_lines_matching: Vec<String>,
+  // regexes for lines to show (e.g. section titles) but which can't be selected.
+  // They're left out when working out the columns.
+  #[serde(default = "Vec::default")]
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub unselectable_lines_matching: Vec<String>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub refresh_frequency: Option<f64>,
+  // how many seconds the main command gets before it's killed. There's no limit
+  // by default, and 0 means no limit either.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub timeout: Option<f64>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub display_command: Option<DisplayCommand>,
+  #[serde(default = "Format::default")]
+  #[serde(skip_serializing_if = "Format::is_table")]
+  pub format: Format,
+  // when set, lines are split on this rather than by looking at how the columns
+  // line up, e.g. `delimiter: ':'` for /etc/passwd
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub delimiter: Option<Delimiter>,
+  // if true, the first line of output (after skipping lines_to_skip) is shown as
+  // the table header and its column names can be used in templates e.g. ${NAMES}
+  #[serde(default = "bool::default")]
+  #[serde(skip_serializing_if = "IsFalse::is_false")]
+  pub header: bool,
+  // when format is json, these are the (optionally dot-separated) keys to show as
+  // columns, in order. If empty, we show every key we come across.
+  #[serde(default = "Vec::default")]
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub columns: Vec<String>,
+  // how to tell that a row after a refetch is the same item as a row before it, so
+  // that the cursor can follow it. Defaults to comparing whole lines.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub row_key: Option<RowKey>,
+  // a regex matched against each line, whose capture groups become the columns,
+  // e.g. `(?P<unit>\w+)\[(?P<pid>\d+)\]` gives the columns `unit` and `pid`
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub row_regex: Option<String>,
+  // if true, lines that don't match row_regex are shown (but can't be selected)
+  // rather than hidden
+  #[serde(default = "bool::default")]
+  #[serde(skip_serializing_if = "IsFalse::is_false")]
+  pub show_unmatched_lines: bool,
+  // for commands that keep running, like `tail -f` or `kubectl get pods -w`: rows
+  // are added as lines arrive rather than once the command exits
+  #[serde(default = "bool::default")]
+  #[serde(skip_serializing_if = "IsFalse::is_false")]
+  pub stream: bool,
+  // when streaming, only the latest `max_rows` rows are kept
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub max_rows: Option<usize>,
+  // for output where each item spans several lines, like `git log` or `ip addr`.
+  // Each record becomes one row, summarised by its first line, and the whole
+  // record is shown in the preview pane if there's no display_command.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub record_separator: Option<RecordSeparator>,
+  // for output that nests, like `tree`, `ps --forest` or `cargo tree`: rows are
+  // shown as a tree that can be folded with the left and right arrow keys, and
+  // templates can refer to the parent row's values e.g. `$parent.0`
+  #[serde(default = "bool::default")]
+  #[serde(skip_serializing_if = "IsFalse::is_false")]
+  pub tree: bool,
+  // how many keybinding commands can run at once. Defaults to one, so that
+  // commands run in the order their keys were pressed.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub max_concurrent_jobs: Option<usize>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub shell: Option<Shell>,
+}
+
+impl Default for Profile {
+  fn default() -> Profile {
+    Profile {
+      name: String::from(""),
+      registered_commands: vec![],
+      key_bindings: vec![],
+      lines_to_skip: 0,
+      skip_trailing_lines: 0,
+      ignore_lines_matching: vec![],
+      unselectable_lines_matching: vec![],
+      refresh_frequency: None,
+      timeout: None,
+      display_command: None,
+      format: Format::Table,
+      delimiter: None,
+      header: false,
+      columns: vec![],
+      row_key: None,
+      row_regex: None,
+      show_unmatched_lines: false,
+      stream: false,
+      max_rows: None,
+      record_separator: None,
+      tree: false,
+      max_concurrent_jobs: None,
+      shell: None,
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RowKey {
+  // e.g. `column: 0` or `column: CONTAINER ID`
+  Column(ColumnRef),
+  // matched against the whole line. If the regex has a capture group, the first group is the key.
+  Regex(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordSeparator {
+  // records are separated by one or more blank lines, e.g. `kubectl describe`
+  BlankLine,
+  // each line matching the regex starts a new record, e.g. `regex: ^commit` for `git log`
+  Regex(String),
+  // each unindented line starts a new record, e.g. `ip addr` or `systemctl status`
+  Indentation,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ColumnRef {
+  Index(usize),
+  Name(String),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+  // whitespace-aligned columns, as output by the likes of `ls -l` and `docker ps`
+  #[default]
+  Table,
+  // a JSON array of objects, or one JSON object per line
+  Json,
+}
+
+impl Format {
+  pub fn is_table(&self) -> bool {
+    *self == Format::Table
+  }
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct KeyBinding {
-  pub key: char,
-  pub command: String,
+  pub key: Key,
+  pub command: CommandTemplate,
   #[serde(default = "bool::default")]
   #[serde(skip_serializing_if = "IsFalse::is_false")]
   pub confirm: bool,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub regex: Option<String>,
+  #[serde(default = "Multi::default")]
+  #[serde(skip_serializing_if = "Multi::is_each")]
+  pub multi: Multi,
+  // if true, we hand the terminal over to the command (e.g. `vim $0`) and pick up
+  // where we left off once it exits
+  #[serde(default = "bool::default")]
+  #[serde(skip_serializing_if = "IsFalse::is_false")]
+  pub interactive: bool,
+  #[serde(default = "Output::default")]
+  #[serde(skip_serializing_if = "Output::is_discard")]
+  pub output: Output,
+  // how many seconds each command gets before it's killed, if there's a limit
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub timeout: Option<f64>,
 }
 
 impl Default for KeyBinding {
   fn default() -> KeyBinding {
     KeyBinding {
-      key: ' ',
-      command: String::from(""),
+      key: Key::from(' '),
+      command: CommandTemplate::from(""),
       confirm: false,
       regex: None,
+      multi: Multi::Each,
+      interactive: false,
+      output: Output::Discard,
+      timeout: None,
     }
   }
 }
 
+// what to do when the keybinding is pressed while several rows are marked
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Multi {
+  // run the command once per marked row
+  #[default]
+  Each,
+  // run the command once, with each placeholder expanded to the values of all marked rows
+  Join,
+}
+
+impl Multi {
+  pub fn is_each(&self) -> bool {
+    *self == Multi::Each
+  }
+}
+
+// what to do with a keybinding's output once its command has run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+  // throw it away, unless the command fails
+  #[default]
+  Discard,
+  // show stdout, stderr and the exit code in a popup that can be scrolled and searched
+  Popup,
+  // show the last line of stdout in the status bar
+  Status,
+  // show stdout in the preview pane
+  Preview,
+}
+
+impl Output {
+  pub fn is_discard(&self) -> bool {
+    *self == Output::Discard
+  }
+}
+
+// A command as written in the config: either a line for the shell, with
+// placeholders quoted for it, or a program and its arguments to run directly,
+// e.g. `[docker, logs, $0]`, where placeholders are substituted as-is.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CommandTemplate {
+  Shell(String),
+  Exec(Vec<String>),
+}
+
+impl From<&str> for CommandTemplate {
+  fn from(command: &str) -> CommandTemplate {
+    CommandTemplate::Shell(String::from(command))
+  }
+}
+
+// `bash` (the default), `sh`, `zsh`, `fish` or any other shell that takes a
+// script with `-c`, `$SHELL` for the user's login shell, or the full command line
+// to run scripts with, where `{}` stands for the script, e.g. `[nu, -c, "{}"]`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Shell {
+  Name(String),
+  Argv(Vec<String>),
+}
+
+pub const DEFAULT_SHELL: &str = "bash";
+
+impl Shell {
+  // `env_shell` is the value of `$SHELL`
+  pub fn argv(&self, env_shell: Option<String>) -> Vec<String> {
+    let name = match self {
+      Shell::Argv(argv) => return argv.clone(),
+      Shell::Name(name) if name == "$SHELL" => {
+        env_shell.unwrap_or_else(|| String::from(DEFAULT_SHELL))
+      }
+      Shell::Name(name) => name.clone(),
+    };
+
+    vec![name, String::from("-c"), String::from("{}")]
+  }
+}
+
 pub trait Command {
-  fn command(&self) -> &str;
+  fn command(&self) -> &CommandTemplate;
   fn regex(&self) -> Option<&str>;
 }
 
 // TODO: is there a better way to do this?
 impl Command for KeyBinding {
-  fn command(&self) -> &str {
-    return &self.command;
+  fn command(&self) -> &CommandTemplate {
+    &self.command
   }
   fn regex(&self) -> Option<&str> {
-    return self.regex.as_deref();
+    self.regex.as_deref()
   }
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct DisplayCommand {
-  pub command: String,
+  pub command: CommandTemplate,
   pub regex: Option<String>,
+  // how many seconds the command gets before it's killed, so that a stuck
+  // command can't hold up the preview pane. 0 means no limit.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub timeout: Option<f64>,
 }
 
 // TODO: is there a better way to do this?
 impl Command for DisplayCommand {
-  fn command(&self) -> &str {
-    return &self.command;
+  fn command(&self) -> &CommandTemplate {
+    &self.command
   }
   fn regex(&self) -> Option<&str> {
-    return self.regex.as_deref();
+    self.regex.as_deref()
+  }
+}
+
+// a timeout setting in seconds, where 0 means there's no timeout
+pub fn timeout_duration(seconds: f64) -> Option<Duration> {
+  if seconds > 0.0 {
+    Some(Duration::from_secs_f64(seconds))
+  } else {
+    None
   }
 }
 
@@ -113,9 +380,61 @@ impl Config {
     })
   }
 
+  // Catches malformed keybinding and display command regexes when the config is
+  // loaded, rather than when a row is selected.
+  pub fn validate(&self) -> Result<(), String> {
+    for profile in &self.profiles {
+      let mut regexes = profile
+        .key_bindings
+        .iter()
+        .filter_map(|kb| Some((format!("keybinding `{}`", kb.key), kb.regex.as_deref()?)))
+        .collect::<Vec<(String, &str)>>();
+      if let Some(regex) = profile
+        .display_command
+        .as_ref()
+        .and_then(|dc| dc.regex.as_deref())
+      {
+        regexes.push((String::from("display_command"), regex));
+      }
+      if let Some(RowKey::Regex(regex)) = &profile.row_key {
+        regexes.push((String::from("row_key"), regex));
+      }
+      if let Some(regex) = &profile.row_regex {
+        regexes.push((String::from("row_regex"), regex));
+      }
+      if let Some(RecordSeparator::Regex(regex)) = &profile.record_separator {
+        regexes.push((String::from("record_separator"), regex));
+      }
+      for regex in &profile.ignore_lines_matching {
+        regexes.push((String::from("ignore_lines_matching"), regex));
+      }
+      for regex in &profile.unselectable_lines_matching {
+        regexes.push((String::from("unselectable_lines_matching"), regex));
+      }
+
+      for (setting, regex) in regexes {
+        Regex::new(regex).map_err(|error| {
+          format!(
+            "Invalid regex `{}` for the {} of profile `{}`: {}",
+            regex, setting, profile.name, error
+          )
+        })?;
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn find_profile_by_name(&self, name: &str) -> Option<&Profile> {
+    self.profiles.iter().find(|p| p.name == name)
+  }
+
+  // the config we write out for new users, which isn't a default as such
+  #[allow(clippy::new_without_default)]
   pub fn new() -> Config {
     // just doing a dummy one for now
     Config {
+      shell: None,
       profiles: vec![
         Profile {
           name: String::from("ls"),
@@ -126,73 +445,77 @@ impl Config {
           ],
           key_bindings: vec![
             KeyBinding {
-              key: 'd',
-              command: String::from("rm -rf $0"),
+              key: Key::from('d'),
+              command: CommandTemplate::from("rm -rf $0"),
               confirm: true,
               ..Default::default()
             },
             KeyBinding {
-              key: 'o',
-              command: String::from("open $0"),
+              key: Key::from('o'),
+              command: CommandTemplate::from("open $0"),
               ..Default::default()
             },
             KeyBinding {
-              key: 'u',
-              command: String::from("cd $0"),
+              key: Key::from('u'),
+              command: CommandTemplate::from("cd $0"),
               ..Default::default()
             },
           ],
           lines_to_skip: 0,
           refresh_frequency: None,
           // display_command: Some(DisplayCommand {
-          //   command: String::from("cat $0"),
+          //   command: CommandTemplate::from("cat $0"),
           //   regex: None,
           // }),
           display_command: None,
+          ..Default::default()
         },
         Profile {
           name: String::from("ls -l"),
           registered_commands: vec![String::from("ls -l")],
           key_bindings: vec![
             KeyBinding {
-              key: 'd',
-              command: String::from("rm -rf $8"),
+              key: Key::from('d'),
+              command: CommandTemplate::from("rm -rf $8"),
               confirm: true,
               ..Default::default()
             },
             KeyBinding {
-              key: 'o',
-              command: String::from("open $8"),
+              key: Key::from('o'),
+              command: CommandTemplate::from("open $8"),
               ..Default::default()
             },
             KeyBinding {
-              key: 'u',
-              command: String::from("cd $8"),
+              key: Key::from('u'),
+              command: CommandTemplate::from("cd $8"),
               ..Default::default()
             },
           ],
-          lines_to_skip: 1,
+          lines_to_skip: 0,
+          // `ls -l` leaves out the total when listing a single file
+          ignore_lines_matching: vec![String::from(r"^total \d+$")],
           refresh_frequency: None,
           display_command: None,
+          ..Default::default()
         },
         Profile {
           name: String::from("git status --short"),
           registered_commands: vec![String::from("git status --short")],
           key_bindings: vec![
             KeyBinding {
-              key: 'A',
-              command: String::from("git add $1"),
+              key: Key::from('A'),
+              command: CommandTemplate::from("git add $1"),
               ..Default::default()
             },
             KeyBinding {
-              key: 'a',
-              command: String::from("git reset $1"),
+              key: Key::from('a'),
+              command: CommandTemplate::from("git reset $1"),
               confirm: false,
               ..Default::default()
             },
             KeyBinding {
-              key: 'd',
-              command: String::from("rm -rf $1"),
+              key: Key::from('d'),
+              command: CommandTemplate::from("rm -rf $1"),
               confirm: true,
               ..Default::default()
             },
@@ -200,61 +523,67 @@ impl Config {
           lines_to_skip: 0,
           refresh_frequency: None,
           // display_command: Some(DisplayCommand {
-          //   command: String::from("git diff $1"),
+          //   command: CommandTemplate::from("git diff $1"),
           //   regex: None,
           // }),
           display_command: None,
+          ..Default::default()
         },
         Profile {
           name: String::from("git status"),
           registered_commands: vec![String::from("git status")],
           key_bindings: vec![
             KeyBinding {
-              key: 'A',
-              command: String::from("git add $0"),
+              key: Key::from('A'),
+              command: CommandTemplate::from("git add $0"),
               ..Default::default()
             },
             KeyBinding {
-              key: 'a',
-              command: String::from("git reset $1"),
+              key: Key::from('a'),
+              command: CommandTemplate::from("git reset $1"),
               confirm: true,
               regex: Some(String::from(".*:\\s+([^\\s]+)")),
               ..Default::default()
             },
             KeyBinding {
-              key: 'd',
-              command: String::from("rm -rf $1"),
+              key: Key::from('d'),
+              command: CommandTemplate::from("rm -rf $1"),
               confirm: true,
               ..Default::default()
             },
           ],
+          // hints like `(use "git add <file>..." to include in what will be committed)`
+          ignore_lines_matching: vec![String::from(r#"^\s*\(use ""#), String::from(r"^\s*$")],
+          // `On branch main`, `Changes to be committed:` and so on. Files are indented.
+          unselectable_lines_matching: vec![String::from(r"^\S")],
           lines_to_skip: 0,
           refresh_frequency: None,
           // display_command: Some(DisplayCommand {
-          //   command: String::from("git diff $1"),
+          //   command: CommandTemplate::from("git diff $1"),
           //   regex: None,
           // }),
           display_command: None,
+          ..Default::default()
         },
         Profile {
           name: String::from("docker ps"),
           registered_commands: vec![String::from("docker ps")],
           key_bindings: vec![
             KeyBinding {
-              key: 's',
-              command: String::from("docker stop $0"),
+              key: Key::from('s'),
+              command: CommandTemplate::from("docker stop $0"),
               confirm: true,
               ..Default::default()
             },
             KeyBinding {
-              key: 'r',
-              command: String::from("docker restart $0"),
+              key: Key::from('r'),
+              command: CommandTemplate::from("docker restart $0"),
               confirm: false,
               ..Default::default()
             },
             KeyBinding {
-              key: 'd',
-              command: String::from("docker kill $0"),
+              key: Key::from('d'),
+              command: CommandTemplate::from("docker kill $0"),
               confirm: true,
               ..Default::default()
             },
@@ -263,41 +592,52 @@ impl Config {
           refresh_frequency: None,
           display_command: None,
           // display_command: Some(DisplayCommand {
-          //   command: String::from("docker inspect $0"),
+          //   command: CommandTemplate::from("docker inspect $0"),
           //   regex: None,
           // }),
+          ..Default::default()
         },
         Profile {
           name: String::from("git branch"),
           registered_commands: vec![String::from("git branch")],
           key_bindings: vec![KeyBinding {
-            key: 'c',
-            command: String::from("git checkout $1"),
+            key: Key::from('c'),
+            command: CommandTemplate::from("git checkout $1"),
             ..Default::default()
           }],
           lines_to_skip: 0,
           refresh_frequency: None,
           // display_command: Some(DisplayCommand {
-          //   command: String::from("git log --oneline $0"),
+          //   command: CommandTemplate::from("git log --oneline $0"),
           //   regex: None,
           // }),
           display_command: None,
+          ..Default::default()
         },
         Profile {
           name: String::from("git log"),
           registered_commands: vec![String::from("git log --oneline")],
-          key_bindings: vec![KeyBinding {
-            key: 'c',
-            command: String::from("git checkout $0"),
-            ..Default::default()
-          }],
+          key_bindings: vec![
+            KeyBinding {
+              key: Key::from('c'),
+              command: CommandTemplate::from("git checkout $0"),
+              ..Default::default()
+            },
+            KeyBinding {
+              key: Key::from('s'),
+              command: CommandTemplate::from("git show $0"),
+              interactive: true,
+              ..Default::default()
+            },
+          ],
           lines_to_skip: 0,
           refresh_frequency: None,
           // display_command: Some(DisplayCommand {
-          //   command: String::from("git show $0"),
+          //   command: CommandTemplate::from("git show $0"),
           //   regex: None,
           // }),
           display_command: None,
+          ..Default::default()
         },
         Profile {
           name: String::from("lsof -iTCP | grep LISTEN"),
@@ -306,16 +646,91 @@ impl Config {
             String::from("lsof -iTCP"),
           ],
           key_bindings: vec![KeyBinding {
-            key: 'd',
-            command: String::from("kill -9 $1"),
+            key: Key::from('d'),
+            command: CommandTemplate::from("kill -9 $1"),
             confirm: true,
             ..Default::default()
           }],
           lines_to_skip: 0,
           refresh_frequency: None,
           display_command: None,
+          ..Default::default()
         },
       ],
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_shell_argv() {
+    let sh_c = |shell: &str| vec![String::from(shell), String::from("-c"), String::from("{}")];
+
+    assert_eq!(Shell::Name(String::from("zsh")).argv(None), sh_c("zsh"));
+    assert_eq!(
+      Shell::Name(String::from("$SHELL")).argv(Some(String::from("/usr/bin/fish"))),
+      sh_c("/usr/bin/fish")
+    );
+    assert_eq!(Shell::Name(String::from("$SHELL")).argv(None), sh_c("bash"));
+  }
+
+  #[test]
+  fn test_command_forms() {
+    let yaml = "shell: [nu, -c, '{}']\nprofiles:\n  - name: ls\n    registered_commands: []\n    shell: sh\n    key_bindings:\n      - key: d\n        command: rm $0\n      - key: e\n        command: [vim, $0]\n";
+    let config = Config::from_yaml(String::from(yaml)).unwrap();
+
+    assert_eq!(
+      config.shell,
+      Some(Shell::Argv(vec![
+        String::from("nu"),
+        String::from("-c"),
+        String::from("{}")
+      ]))
+    );
+    let profile = &config.profiles[0];
+    assert_eq!(profile.shell, Some(Shell::Name(String::from("sh"))));
+    assert_eq!(
+      profile.key_bindings[0].command,
+      CommandTemplate::from("rm $0")
+    );
+    assert_eq!(
+      profile.key_bindings[1].command,
+      CommandTemplate::Exec(vec![String::from("vim"), String::from("$0")])
+    );
+  }
+
+  #[test]
+  fn test_validate_regexes() {
+    let yaml = "profiles:\n  - name: ls\n    registered_commands: []\n    key_bindings:\n      - key: d\n        command: rm $1\n        regex: '(unclosed'\n";
+    let config = Config::from_yaml(String::from(yaml)).unwrap();
+
+    assert!(config
+      .validate()
+      .unwrap_err()
+      .starts_with("Invalid regex `(unclosed` for the keybinding `d` of profile `ls`"));
+
+    for setting in [
+      "row_key:\n      regex: '(unclosed'",
+      "row_regex: '(unclosed'",
+      "record_separator:\n      regex: '(unclosed'",
+      "ignore_lines_matching: ['(unclosed']",
+      "unselectable_lines_matching: ['(unclosed']",
+    ] {
+      let yaml = format!(
+        "profiles:\n  - name: ls\n    registered_commands: []\n    key_bindings: []\n    {}\n",
+        setting
+      );
+      let config = Config::from_yaml(yaml).unwrap();
+      let name = setting.split(':').next().unwrap();
+      assert!(config.validate().unwrap_err().starts_with(&format!(
+        "Invalid regex `(unclosed` for the {} of profile `ls`",
+        name
+      )));
+    }
+    assert_eq!(Config::new().validate(), Ok(()));
+  }
+}
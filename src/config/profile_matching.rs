@@ -5,7 +5,7 @@ use regex::Regex;
 pub fn command_matches(command: &str, pattern: &str) -> bool {
   let regex_str_inner = pattern
     .split("*")
-    .map(|chunk| regex::escape(chunk))
+    .map(regex::escape)
     .collect::<Vec<String>>()
     .join(".*");
 
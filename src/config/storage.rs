@@ -18,13 +18,13 @@ where
     Some(parent) => (fs::create_dir_all(home.join(parent)))?,
     None => (fs::create_dir_all(home))?,
   }
-  Ok(PathBuf::from(home.join(path.as_ref())))
+  Ok(home.join(path.as_ref()))
 }
 
 pub fn config_path(dir_env_var: Option<String>) -> Result<PathBuf, Box<dyn Error>> {
   let config_dir = config_dir(dir_env_var);
 
-  Ok(write_file(&PathBuf::from(config_dir), "config.yml")?)
+  Ok(write_file(&config_dir, "config.yml")?)
 }
 
 fn config_dir(dir_env_var: Option<String>) -> PathBuf {
@@ -1,7 +1,12 @@
 use crossterm::event::{self, Event as CEvent, KeyCode, KeyEvent, KeyModifiers};
 use std::{
   error::Error,
-  sync::mpsc::{self, Receiver, Sender},
+  io::{self, BufRead, BufReader, Read},
+  sync::{
+    atomic::Ordering,
+    mpsc::{self, Receiver, Sender},
+    Arc,
+  },
   thread,
   time::{Duration, Instant},
 };
@@ -9,11 +14,14 @@ use std::{
 use ticker::Ticker;
 
 use crate::{
-  app::{App, FocusedPanel},
-  command, os_commands,
-  parse::{self, Row},
-  template,
-  terminal_manager::TerminalManager,
+  app::{App, CommandBatch, FocusedPanel},
+  command::{self, CommandLine, CommandOutput, StreamingCommand},
+  config::{self, Output},
+  jobs::JobStatus,
+  os_commands,
+  output_view::OutputView,
+  parse::{self, ParseOptions, Parsed},
+  terminal_manager::{InputPause, TerminalManager},
   ui,
 };
 
@@ -21,25 +29,36 @@ enum Event<I> {
   Input(I),
   Tick,
   RefetchData(bool), // the bool here is true if it's a background refetch
-  RowsLoaded(Vec<Row>),
-  Error(String),
+  RowsLoaded(Parsed),
+  FetchFailed(String),
+  PreviewLoaded(CommandLine, Result<String, String>), // the display command and its output
+  LinesStreamed(Vec<String>),
+  StreamEnded(Result<(), String>),
+  JobFinished(usize, Vec<CommandOutput>), // the job's id and the output of each command it ran
+}
+
+// how long the cursor needs to rest on a row before we run its display command
+const PREVIEW_DEBOUNCE: Duration = Duration::from_millis(100);
+
+// how long we collect streamed lines for before handing them over, so that we're
+// not redrawing for every line
+const STREAM_BATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+enum StreamMessage {
+  Line(String),
+  Ended(Result<(), String>),
 }
 
 pub fn run(mut app: App) -> Result<(), Box<dyn Error>> {
   // select the first row (no rows will be loaded at this point but that's okay)
   app.table.next();
 
-  let lines_to_skip = if app.args.lines_to_skip != 0 {
-    app.args.lines_to_skip
-  } else {
-    match app.profile {
-      Some(profile) => profile.lines_to_skip,
-      None => 0,
-    }
-  };
+  let parse_options = app.parse_options();
 
   // comparing two floating points directly: probably not advisable?
-  let refresh_frequency = if app.args.refresh_frequency != 0.0 {
+  let refresh_frequency = if !app.can_refetch() {
+    0.0
+  } else if app.args.refresh_frequency != 0.0 {
     app.args.refresh_frequency
   } else {
     match app.profile {
@@ -53,11 +72,17 @@ pub fn run(mut app: App) -> Result<(), Box<dyn Error>> {
   let (tx, rx) = mpsc::channel();
   let (loading_tx, loading_rx) = mpsc::channel();
 
-  poll_events(&tx);
+  poll_events(&tx, terminal_manager.input_pause.clone());
   poll_refetches(&tx, refresh_frequency);
   poll_loading(&tx, loading_rx);
+  let preview_tx = poll_previews(&tx, app.display_command_timeout());
 
-  tx.send(Event::RefetchData(false)).unwrap();
+  match (app.args.command.clone(), app.is_streaming()) {
+    (Some(command), true) => stream_command(&tx, &command),
+    (Some(_), false) => tx.send(Event::RefetchData(false)).unwrap(),
+    (None, true) => stream_lines(BufReader::new(io::stdin()), &tx, || Ok(())),
+    (None, false) => read_stdin(&mut app, &tx, &parse_options, &loading_tx),
+  }
 
   loop {
     terminal_manager
@@ -70,8 +95,9 @@ pub fn run(mut app: App) -> Result<(), Box<dyn Error>> {
         &mut app,
         &mut terminal_manager,
         &tx,
-        lines_to_skip,
+        &parse_options,
         &loading_tx,
+        &preview_tx,
       )
     };
 
@@ -90,17 +116,26 @@ pub fn run(mut app: App) -> Result<(), Box<dyn Error>> {
   Ok(())
 }
 
-fn poll_events(tx: &Sender<Event<KeyEvent>>) {
+fn poll_events(tx: &Sender<Event<KeyEvent>>, input_pause: Arc<InputPause>) {
   let tick_rate = Duration::from_millis(10000); // TODO: do we actually need this?
+
+  // we can't respond to a pause request while we're blocked on a poll, so we keep our polls short
+  let max_poll_duration = Duration::from_millis(50);
   let tx_clone = tx.clone();
 
   thread::spawn(move || {
     let mut last_tick = Instant::now();
     loop {
+      if input_pause.check() {
+        thread::sleep(max_poll_duration);
+        continue;
+      }
+
       // poll for tick rate duration, if no events, sent tick event.
       let timeout = tick_rate
         .checked_sub(last_tick.elapsed())
-        .unwrap_or_else(|| Duration::from_secs(0));
+        .unwrap_or_else(|| Duration::from_secs(0))
+        .min(max_poll_duration);
       if event::poll(timeout).unwrap() {
         if let CEvent::Key(key) = event::read().unwrap() {
           tx_clone.send(Event::Input(key)).unwrap();
@@ -147,11 +182,8 @@ fn poll_loading(tx: &Sender<Event<KeyEvent>>, loading_rx: Receiver<bool>) {
           Err(e) => panic!("Unexpected error: {:?}", e),
         }
       } else {
-        match loading_rx.recv() {
-          Ok(v) => v,
-          // we get this error when we quit the application so we're just returning false for now
-          Err(_) => false,
-        }
+        // we get an error when we quit the application so we're just returning false for now
+        loading_rx.recv().unwrap_or_default()
       };
 
       if is_loading {
@@ -161,22 +193,58 @@ fn poll_loading(tx: &Sender<Event<KeyEvent>>, loading_rx: Receiver<bool>) {
   });
 }
 
+// Display commands are run one at a time on this thread, and only once the user
+// has stopped scrolling. Requests that are superseded while we wait are dropped.
+fn poll_previews(tx: &Sender<Event<KeyEvent>>, timeout: Option<Duration>) -> Sender<CommandLine> {
+  let (preview_tx, preview_rx) = mpsc::channel::<CommandLine>();
+  let tx_clone = tx.clone();
+
+  thread::spawn(move || {
+    while let Ok(mut command) = preview_rx.recv() {
+      loop {
+        match preview_rx.recv_timeout(PREVIEW_DEBOUNCE) {
+          Ok(newer_command) => command = newer_command,
+          Err(mpsc::RecvTimeoutError::Timeout) => break,
+          Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        }
+      }
+
+      let result = command::run_command(&command, timeout);
+      if tx_clone
+        .send(Event::PreviewLoaded(command, result))
+        .is_err()
+      {
+        return;
+      }
+    }
+  });
+
+  preview_tx
+}
+
+fn update_preview(app: &mut App, preview_tx: &Sender<CommandLine>) {
+  if let Some(command) = app.on_select() {
+    preview_tx.send(command).unwrap();
+  }
+}
+
 fn handle_event(
   event: Event<KeyEvent>,
   app: &mut App,
   terminal_manager: &mut TerminalManager,
   tx: &Sender<Event<KeyEvent>>,
-  lines_to_skip: usize,
+  parse_options: &ParseOptions,
   loading_tx: &Sender<bool>,
+  preview_tx: &Sender<CommandLine>,
 ) -> Result<bool, Box<dyn Error>> {
-  fn navigate_down(app: &mut App) {
+  let navigate_down = |app: &mut App| {
     app.table.next();
-    app.on_select();
-  }
-  fn navigate_up(app: &mut App) {
+    update_preview(app, preview_tx);
+  };
+  let navigate_up = |app: &mut App| {
     app.table.previous();
-    app.on_select();
-  }
+    update_preview(app, preview_tx);
+  };
   match event {
     Event::Input(event) => {
       if event.code == KeyCode::Char('c') && event.modifiers == KeyModifiers::CONTROL {
@@ -185,41 +253,83 @@ fn handle_event(
       }
 
       match app.focused_panel {
+        // the profile's keybindings take precedence over our own keys, so that a
+        // binding for e.g. `space` or `alt+j` doesn't get swallowed
+        FocusedPanel::Table if app.key_binding(&event).is_some() => {
+          if let Some(batch) = handle_keybinding_press(app, &event) {
+            run_batch(app, terminal_manager, loading_tx, tx, batch)?;
+          }
+        }
         FocusedPanel::Table => match event.code {
           KeyCode::Char('q') => {
             terminal_manager.teardown()?;
             return Ok(false);
           }
           KeyCode::Esc => {
-            app.reset_filter_text();
+            if app.has_marks() {
+              app.clear_marks();
+            } else {
+              app.reset_filter_text();
+              update_preview(app, preview_tx);
+            }
+          }
+          KeyCode::Char(' ') => {
+            app.toggle_mark();
+            navigate_down(app);
+          }
+          KeyCode::Char('V') => app.toggle_visual_mode(),
+          KeyCode::Char('x') if event.modifiers == KeyModifiers::CONTROL => {
+            app.jobs.cancel_all(Instant::now());
+            if command::cancel_running() > 0 {
+              app.status_text = Some(String::from("Cancelling..."));
+              // a display command may have been cancelled along with everything else
+              app.invalidate_previews();
+              update_preview(app, preview_tx);
+            }
           }
           KeyCode::Down | KeyCode::Char('j') => navigate_down(app),
           KeyCode::Char('n') if event.modifiers == KeyModifiers::CONTROL => navigate_down(app),
           KeyCode::Up | KeyCode::Char('k') => navigate_up(app),
+          KeyCode::Left if app.tree_column.is_some() => {
+            app.collapse_selected();
+            update_preview(app, preview_tx);
+          }
+          KeyCode::Right if app.tree_column.is_some() => {
+            app.expand_selected();
+            update_preview(app, preview_tx);
+          }
           KeyCode::Char('p') if event.modifiers == KeyModifiers::CONTROL => navigate_up(app),
           KeyCode::Char('/') => {
             app.focused_panel = FocusedPanel::Search;
           }
+          KeyCode::Char('t') if event.modifiers == KeyModifiers::CONTROL => {
+            let newest = app.jobs.jobs.len().checked_sub(1);
+            app.jobs.table.state.select(newest);
+            app.focused_panel = FocusedPanel::Jobs;
+          }
           KeyCode::Char('$') => {
             // TODO: wonder if the typical user would prefer opening the file or switching to vim to edit it? If they do want to open it, we probably need an OS-specific command to be entered here.
-            run_command(
+            run_commands(
               app,
               loading_tx,
               tx,
-              os_commands::open_command(app.config_path.to_str().unwrap()),
+              vec![CommandLine::Shell(os_commands::open_command(
+                app.config_path.to_str().unwrap(),
+              ))],
+              Output::Discard,
+              None,
             );
           }
-          KeyCode::Char(c) => {
-            handle_keybinding_press(app, loading_tx, tx, c);
-          }
-          _ => (),
+          _ => {}
         },
         FocusedPanel::Search => match event.code {
           KeyCode::Backspace => {
             app.pop_filter_text_char();
+            update_preview(app, preview_tx);
           }
           KeyCode::Esc => {
             app.reset_filter_text();
+            update_preview(app, preview_tx);
             app.focused_panel = FocusedPanel::Table;
           }
           KeyCode::Enter => {
@@ -227,6 +337,7 @@ fn handle_event(
           }
           KeyCode::Char(c) => {
             app.push_filter_text_char(c);
+            update_preview(app, preview_tx);
           }
           _ => (),
         },
@@ -240,12 +351,12 @@ fn handle_event(
           }
           _ => {}
         },
-        FocusedPanel::ConfirmationPopup(ref command) => match event.code {
+        FocusedPanel::ConfirmationPopup(ref batch) => match event.code {
           KeyCode::Enter => {
-            // interesting lesson here: if I have command.clone() in the call to run_command itself (i.e. no intermediate variable) I get an error for borrowing app twice because I borrow it once to get the command and then I pass it as a mutable reference into the run_command function. With this intermediate variable, rust knows we no longer need the reference to app so I'm okay to go ahead and get the mutable reference.
-            let cloned_command = command.clone();
-            run_command(app, loading_tx, tx, cloned_command);
+            // interesting lesson here: if I have batch.clone() in the call to run_batch itself (i.e. no intermediate variable) I get an error for borrowing app twice because I borrow it once to get the batch and then I pass it as a mutable reference into the run_batch function. With this intermediate variable, rust knows we no longer need the reference to app so I'm okay to go ahead and get the mutable reference.
+            let cloned_batch = batch.clone();
             app.focused_panel = FocusedPanel::Table;
+            run_batch(app, terminal_manager, loading_tx, tx, cloned_batch)?;
           }
           KeyCode::Char('q') => {
             terminal_manager.teardown()?;
@@ -256,6 +367,76 @@ fn handle_event(
           }
           _ => {}
         },
+        FocusedPanel::Jobs => match event.code {
+          KeyCode::Char('q') => {
+            terminal_manager.teardown()?;
+            return Ok(false);
+          }
+          KeyCode::Esc => {
+            app.focused_panel = FocusedPanel::Table;
+          }
+          KeyCode::Down | KeyCode::Char('j') => app.jobs.table.next(),
+          KeyCode::Up | KeyCode::Char('k') => app.jobs.table.previous(),
+          KeyCode::Enter => {
+            if let Some(job) = app.jobs.selected() {
+              if job.status.is_finished() {
+                app.focused_panel = FocusedPanel::JobOutputPopup(OutputView::new(&job.outputs));
+              }
+            }
+          }
+          KeyCode::Char('r') => {
+            if let Some(job) = app.jobs.selected() {
+              let (commands, output, timeout) = (job.commands.clone(), job.output, job.timeout);
+              run_commands(app, loading_tx, tx, commands, output, timeout);
+              let newest = app.jobs.jobs.len().checked_sub(1);
+              app.jobs.table.state.select(newest);
+            }
+          }
+          KeyCode::Char('x') => {
+            if let Some(id) = app.jobs.selected().map(|job| job.id) {
+              if app.jobs.cancel(id, Instant::now()) {
+                command::cancel_job(id);
+              }
+              start_jobs(app, loading_tx, tx);
+            }
+          }
+          _ => {}
+        },
+        FocusedPanel::OutputPopup(ref mut view) | FocusedPanel::JobOutputPopup(ref mut view)
+          if view.search.is_some() =>
+        {
+          match event.code {
+            KeyCode::Backspace => view.pop_search_char(),
+            KeyCode::Esc => view.cancel_search(),
+            KeyCode::Enter => view.finish_search(),
+            KeyCode::Char(c) => view.push_search_char(c),
+            _ => {}
+          }
+        }
+        FocusedPanel::OutputPopup(ref mut view) | FocusedPanel::JobOutputPopup(ref mut view) => {
+          match event.code {
+            KeyCode::Char('q') => {
+              terminal_manager.teardown()?;
+              return Ok(false);
+            }
+            KeyCode::Esc => {
+              app.focused_panel = match app.focused_panel {
+                FocusedPanel::JobOutputPopup(_) => FocusedPanel::Jobs,
+                _ => FocusedPanel::Table,
+              };
+            }
+            KeyCode::Down | KeyCode::Char('j') => view.scroll_down(1),
+            KeyCode::Up | KeyCode::Char('k') => view.scroll_up(1),
+            KeyCode::PageDown => view.scroll_down(view.page_size()),
+            KeyCode::PageUp => view.scroll_up(view.page_size()),
+            KeyCode::Char('g') => view.scroll_to_top(),
+            KeyCode::Char('G') => view.scroll_to_bottom(),
+            KeyCode::Char('/') => view.start_search(),
+            KeyCode::Char('n') => view.next_match(),
+            KeyCode::Char('N') => view.previous_match(),
+            _ => {}
+          }
+        }
       }
     }
 
@@ -263,96 +444,411 @@ fn handle_event(
       app.on_tick();
     }
     Event::RefetchData(background) => {
-      refetch_data(app, tx, lines_to_skip, loading_tx, background);
+      if !background {
+        app.invalidate_previews();
+      }
+
+      // Only one fetch runs at a time so that a slow fetch can't come back after a
+      // newer one and clobber its rows.
+      if !app.can_refetch() {
+        // there's nothing to refetch, but we've still got to clear the status of
+        // whatever command got us here
+        if !app.fetch_in_flight {
+          app.status_text = None;
+          stop_loading(app, loading_tx);
+        }
+      } else if !app.fetch_in_flight {
+        refetch_data(app, tx, parse_options, loading_tx, background);
+      } else if background {
+        app.skipped_fetches += 1;
+        app.status_text = Some(format!(
+          "Skipped {} refresh(es): still waiting on the previous fetch",
+          app.skipped_fetches
+        ));
+      } else {
+        // we've just run a command so whatever's in flight may already be out of date
+        app.refetch_queued = true;
+      }
     }
-    Event::RowsLoaded(rows) => {
-      on_rows_loaded(app, loading_tx, rows);
+    Event::RowsLoaded(parsed) => {
+      on_rows_loaded(app, loading_tx, parsed);
+      update_preview(app, preview_tx);
+      on_fetch_finished(app, tx, parse_options, loading_tx);
     }
-    Event::Error(error) => {
+    Event::FetchFailed(error) => {
       app.focused_panel = FocusedPanel::ErrorPopup(error);
       app.status_text = None;
+      stop_loading(app, loading_tx);
+      on_fetch_finished(app, tx, parse_options, loading_tx);
+    }
+    Event::PreviewLoaded(command, result) => {
+      app.on_preview_loaded(command, result);
+    }
+    Event::LinesStreamed(lines) => {
+      if let Err(error) = app.on_lines_streamed(lines) {
+        app.focused_panel = FocusedPanel::ErrorPopup(error);
+      }
+      update_preview(app, preview_tx);
+    }
+    Event::StreamEnded(result) => match result {
+      Ok(()) => app.command_result = Some(String::from("Command exited")),
+      Err(error) => app.focused_panel = FocusedPanel::ErrorPopup(error),
+    },
+    Event::JobFinished(id, outputs) => {
+      on_job_finished(app, loading_tx, tx, id, outputs);
     }
   }
 
   Ok(true)
 }
 
-fn handle_keybinding_press(
+// returns the commands to run straight away, if any. If the keybinding requires
+// confirmation, we instead show the confirmation popup.
+fn handle_keybinding_press(app: &mut App, event: &KeyEvent) -> Option<CommandBatch> {
+  let binding = app.key_binding(event)?;
+
+  let commands = match app.resolve_commands(binding) {
+    Ok(commands) => commands,
+    Err(error) => {
+      app.focused_panel = FocusedPanel::ErrorPopup(error);
+      return None;
+    }
+  };
+
+  if commands.is_empty() {
+    return None;
+  }
+
+  let batch = CommandBatch {
+    commands,
+    interactive: binding.interactive,
+    output: binding.output,
+    timeout: binding.timeout.and_then(config::timeout_duration),
+  };
+
+  if binding.confirm {
+    app.focused_panel = FocusedPanel::ConfirmationPopup(batch);
+    None
+  } else {
+    Some(batch)
+  }
+}
+
+fn run_batch(
   app: &mut App,
+  terminal_manager: &mut TerminalManager,
   loading_tx: &Sender<bool>,
   tx: &Sender<Event<KeyEvent>>,
-  c: char,
-) -> Option<()> {
-  let binding = app.profile?.key_bindings.iter().find(|&kb| kb.key == c)?;
-
-  let command = template::resolve_command(binding, app.get_selected_row()?);
+  batch: CommandBatch,
+) -> Result<(), Box<dyn Error>> {
+  app.clear_marks();
 
-  if binding.confirm {
-    app.focused_panel = FocusedPanel::ConfirmationPopup(command);
+  if batch.interactive {
+    run_interactive_commands(app, terminal_manager, tx, batch.commands)
   } else {
-    run_command(app, loading_tx, tx, command);
+    run_commands(
+      app,
+      loading_tx,
+      tx,
+      batch.commands,
+      batch.output,
+      batch.timeout,
+    );
+    Ok(())
   }
+}
+
+// Unlike other commands, these run on the main thread: there's nothing for us to
+// draw while the command has the terminal anyway.
+fn run_interactive_commands(
+  app: &mut App,
+  terminal_manager: &mut TerminalManager,
+  tx: &Sender<Event<KeyEvent>>,
+  commands: Vec<CommandLine>,
+) -> Result<(), Box<dyn Error>> {
+  terminal_manager.suspend()?;
 
-  Some(())
+  let result = commands
+    .iter()
+    .try_for_each(command::run_interactive_command);
+
+  terminal_manager.resume()?;
+
+  match result {
+    Ok(()) => tx.send(Event::RefetchData(false))?,
+    Err(error) => app.focused_panel = FocusedPanel::ErrorPopup(error),
+  }
+
+  Ok(())
 }
 
-fn run_command(
+// Keybinding commands are queued up as a job, which runs once there's room for
+// it. Within a job, commands are run one after the other, stopping at the first
+// failure.
+fn run_commands(
   app: &mut App,
   loading_tx: &Sender<bool>,
   tx: &Sender<Event<KeyEvent>>,
-  command: String,
+  commands: Vec<CommandLine>,
+  output: Output,
+  timeout: Option<Duration>,
 ) {
-  app.status_text = Some(format!("Running command: {}", command));
-  loading_tx.send(true).unwrap();
+  app.command_result = None;
+  app.jobs.enqueue(commands, output, timeout);
+  start_jobs(app, loading_tx, tx);
+}
 
-  let tx_clone = tx.clone();
-  thread::spawn(move || match command::run_command(&command) {
-    Ok(_) => tx_clone.send(Event::RefetchData(false)).unwrap(),
-    Err(error) => tx_clone.send(Event::Error(error)).unwrap(),
-  });
+fn start_jobs(app: &mut App, loading_tx: &Sender<bool>, tx: &Sender<Event<KeyEvent>>) {
+  for id in app.jobs.start_ready(Instant::now()) {
+    let job = app.jobs.get(id).unwrap();
+    let commands = job.commands.clone();
+    let timeout = job.timeout;
+    let cancelled = job.cancelled.clone();
+    loading_tx.send(true).unwrap();
+
+    let tx_clone = tx.clone();
+    thread::spawn(move || {
+      let mut outputs = vec![];
+      for command in commands {
+        if cancelled.load(Ordering::SeqCst) {
+          break;
+        }
+        let output = command::run_job_command(&command, id, timeout);
+        let success = output.success();
+        outputs.push(output);
+        if !success {
+          break;
+        }
+      }
+
+      tx_clone.send(Event::JobFinished(id, outputs)).ok();
+    });
+  }
+}
+
+// A popup shows whatever happened, failure or not. The other modes only show
+// output on success and otherwise fall back to the error popup. Nothing is
+// shown for a cancelled job.
+fn on_job_finished(
+  app: &mut App,
+  loading_tx: &Sender<bool>,
+  tx: &Sender<Event<KeyEvent>>,
+  id: usize,
+  outputs: Vec<CommandOutput>,
+) {
+  let (status, output) = match app.jobs.finish(id, outputs, Instant::now()) {
+    Some(job) => (job.status, job.output),
+    None => return,
+  };
+  let outputs = &app.jobs.get(id).unwrap().outputs;
+
+  match (status, output) {
+    (JobStatus::Cancelled, _) => {}
+    (_, Output::Popup) => {
+      app.focused_panel = FocusedPanel::OutputPopup(OutputView::new(outputs));
+    }
+    (JobStatus::Succeeded, Output::Status) => {
+      app.command_result = outputs
+        .iter()
+        .flat_map(|output| output.stdout.lines())
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .map(|line| line.trim().to_owned());
+    }
+    (JobStatus::Succeeded, Output::Preview) => {
+      // the output stays up until another row is selected
+      app.preview_command = None;
+      app.selected_item_content = Ok(
+        outputs
+          .iter()
+          .map(|output| output.stdout.as_str())
+          .collect::<Vec<&str>>()
+          .join("\n"),
+      );
+    }
+    (JobStatus::Succeeded, Output::Discard) => {}
+    _ => {
+      if let Some(failure) = outputs.iter().find(|output| !output.success()) {
+        let error = if outputs.len() > 1 {
+          format!("Command `{}` failed:\n{}", failure.command, failure.error())
+        } else {
+          failure.error()
+        };
+        app.focused_panel = FocusedPanel::ErrorPopup(error);
+      }
+    }
+  }
+
+  start_jobs(app, loading_tx, tx);
+
+  // refetching would replace the preview with the selected row's display command
+  if status == JobStatus::Succeeded && output != Output::Preview {
+    tx.send(Event::RefetchData(false)).unwrap();
+  } else {
+    app.status_text = None;
+    stop_loading(app, loading_tx);
+  }
+}
+
+// the spinner keeps going while there are jobs left
+fn stop_loading(app: &App, loading_tx: &Sender<bool>) {
+  if !app.jobs.is_busy() {
+    loading_tx.send(false).unwrap();
+  }
 }
 
 fn refetch_data(
   app: &mut App,
   tx: &Sender<Event<KeyEvent>>,
-  lines_to_skip: usize,
+  parse_options: &ParseOptions,
   loading_tx: &Sender<bool>,
   background: bool,
 ) {
-  let command = app.args.command.clone();
+  let command = match &app.args.command {
+    Some(command) => command.clone(),
+    None => return,
+  };
+  app.fetch_in_flight = true;
   app.status_text = Some(if background {
     String::from("")
   } else {
-    format!("Running command: {} (if this is taking a while the program might be continuously streaming data which is not yet supported)", command)
+    format!("Running command: {} (if this is taking a while the program might be continuously streaming data, in which case try `--stream`)", command)
   });
   loading_tx.send(true).unwrap();
 
+  let timeout = app.command_timeout();
   let tx_clone = tx.clone();
+  let parse_options = parse_options.clone();
   thread::spawn(move || {
-    let rows = get_rows_from_command(&command, lines_to_skip);
+    let parsed = get_rows_from_command(&command, timeout, &parse_options);
 
-    match rows {
-      Ok(rows) => tx_clone.send(Event::RowsLoaded(rows)).unwrap(),
-      Err(error) => tx_clone.send(Event::Error(error)).unwrap(),
+    match parsed {
+      Ok(parsed) => tx_clone.send(Event::RowsLoaded(parsed)).unwrap(),
+      Err(error) => tx_clone.send(Event::FetchFailed(error)).unwrap(),
     }
   });
 }
 
-fn get_rows_from_command(command: &str, skip_lines: usize) -> Result<Vec<Row>, String> {
-  let output = command::run_command(command)?;
+// The keyboard is unaffected by stdin being a pipe: crossterm reads keypresses
+// from /dev/tty when stdin isn't a terminal.
+fn read_stdin(
+  app: &mut App,
+  tx: &Sender<Event<KeyEvent>>,
+  parse_options: &ParseOptions,
+  loading_tx: &Sender<bool>,
+) {
+  app.fetch_in_flight = true;
+  app.status_text = Some(String::from("Reading from stdin"));
+  loading_tx.send(true).unwrap();
+
+  let tx_clone = tx.clone();
+  let parse_options = parse_options.clone();
+  thread::spawn(move || {
+    let mut output = vec![];
+    let parsed = match io::stdin().read_to_end(&mut output) {
+      Ok(_) => parse::parse(
+        String::from_utf8_lossy(&output).into_owned(),
+        &parse_options,
+      ),
+      Err(error) => Err(format!("Failed to read from stdin: {}", error)),
+    };
+
+    match parsed {
+      Ok(parsed) => tx_clone.send(Event::RowsLoaded(parsed)).unwrap(),
+      Err(error) => tx_clone.send(Event::FetchFailed(error)).unwrap(),
+    }
+  });
+}
+
+fn stream_command(tx: &Sender<Event<KeyEvent>>, command: &str) {
+  match StreamingCommand::spawn(&CommandLine::Shell(command.to_owned())) {
+    Ok((streaming_command, stdout)) => {
+      stream_lines(BufReader::new(stdout), tx, move || streaming_command.wait())
+    }
+    Err(error) => tx.send(Event::StreamEnded(Err(error))).unwrap(),
+  }
+}
+
+// Reads lines as they arrive and sends them over in batches. `on_end` is called
+// once the output is exhausted, to find out whether things went well.
+fn stream_lines<R, F>(mut reader: R, tx: &Sender<Event<KeyEvent>>, on_end: F)
+where
+  R: BufRead + Send + 'static,
+  F: FnOnce() -> Result<(), String> + Send + 'static,
+{
+  let (message_tx, message_rx) = mpsc::channel();
+
+  thread::spawn(move || {
+    let mut line = vec![];
+    // read_until rather than lines() so that invalid UTF-8 doesn't end the stream
+    while let Ok(n) = reader.read_until(b'\n', &mut line) {
+      if n == 0 {
+        break;
+      }
+      let text = String::from_utf8_lossy(&line);
+      let text = text.trim_end_matches(&['\n', '\r'][..]).to_owned();
+      if message_tx.send(StreamMessage::Line(text)).is_err() {
+        return;
+      }
+      line.clear();
+    }
+
+    message_tx.send(StreamMessage::Ended(on_end())).ok();
+  });
+
+  let tx_clone = tx.clone();
+  thread::spawn(move || {
+    while let Ok(message) = message_rx.recv() {
+      thread::sleep(STREAM_BATCH_INTERVAL);
+
+      let mut lines = vec![];
+      let mut ended = None;
+      for message in std::iter::once(message).chain(message_rx.try_iter()) {
+        match message {
+          StreamMessage::Line(line) => lines.push(line),
+          StreamMessage::Ended(result) => ended = Some(result),
+        }
+      }
+
+      if !lines.is_empty() && tx_clone.send(Event::LinesStreamed(lines)).is_err() {
+        return;
+      }
+      if let Some(result) = ended {
+        tx_clone.send(Event::StreamEnded(result)).ok();
+        return;
+      }
+    }
+  });
+}
+
+fn on_fetch_finished(
+  app: &mut App,
+  tx: &Sender<Event<KeyEvent>>,
+  parse_options: &ParseOptions,
+  loading_tx: &Sender<bool>,
+) {
+  app.fetch_in_flight = false;
+  app.skipped_fetches = 0;
+
+  if app.refetch_queued {
+    app.refetch_queued = false;
+    refetch_data(app, tx, parse_options, loading_tx, false);
+  }
+}
 
-  let trimmed_output = output
-    .lines()
-    .skip(skip_lines)
-    .collect::<Vec<&str>>()
-    .join("\n");
+fn get_rows_from_command(
+  command: &str,
+  timeout: Option<Duration>,
+  parse_options: &ParseOptions,
+) -> Result<Parsed, String> {
+  let output = command::run_command(&CommandLine::Shell(command.to_owned()), timeout)?;
 
-  Ok(parse::parse(trimmed_output))
+  parse::parse(output, parse_options)
 }
 
-fn on_rows_loaded(app: &mut App, loading_tx: &Sender<bool>, rows: Vec<Row>) {
-  app.update_rows(rows);
+fn on_rows_loaded(app: &mut App, loading_tx: &Sender<bool>, parsed: Parsed) {
+  app.update_rows(parsed);
 
   app.status_text = None;
-  loading_tx.send(false).unwrap();
+  stop_loading(app, loading_tx);
 }
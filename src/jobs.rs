@@ -0,0 +1,390 @@
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::{Duration, Instant},
+};
+
+use crate::{
+  command::{CommandLine, CommandOutput},
+  config::Output,
+  stateful_table::StatefulTable,
+};
+
+// finished jobs beyond this many are forgotten, oldest first
+const MAX_FINISHED_JOBS: usize = 100;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum JobStatus {
+  Queued,
+  Running,
+  Succeeded,
+  Failed,
+  TimedOut,
+  Cancelled,
+}
+
+impl JobStatus {
+  pub fn is_finished(&self) -> bool {
+    !matches!(self, JobStatus::Queued | JobStatus::Running)
+  }
+}
+
+// the commands resolved from a single keypress, run one after the other and
+// stopping at the first failure
+pub struct Job {
+  pub id: usize,
+  pub commands: Vec<CommandLine>,
+  pub output: Output,
+  // how long each command gets before it's killed
+  pub timeout: Option<Duration>,
+  pub status: JobStatus,
+  pub started_at: Option<Instant>,
+  pub finished_at: Option<Instant>,
+  pub outputs: Vec<CommandOutput>,
+  // checked by the job's thread before each command, so that a cancelled job
+  // doesn't go on to its next command
+  pub cancelled: Arc<AtomicBool>,
+}
+
+impl Job {
+  pub fn duration(&self, now: Instant) -> Option<Duration> {
+    let started_at = self.started_at?;
+    Some(self.finished_at.unwrap_or(now) - started_at)
+  }
+
+  // the exit code of the last command that ran
+  pub fn exit_code(&self) -> Option<i32> {
+    self.outputs.last()?.exit_code
+  }
+}
+
+pub struct Jobs {
+  // oldest first
+  pub jobs: Vec<Job>,
+  next_id: usize,
+  max_concurrent: usize,
+  // the cursor in the jobs panel
+  pub table: StatefulTable,
+}
+
+impl Jobs {
+  pub fn new(max_concurrent: usize) -> Jobs {
+    Jobs {
+      jobs: vec![],
+      next_id: 0,
+      max_concurrent: max_concurrent.max(1),
+      table: StatefulTable::new(0),
+    }
+  }
+
+  pub fn enqueue(
+    &mut self,
+    commands: Vec<CommandLine>,
+    output: Output,
+    timeout: Option<Duration>,
+  ) -> usize {
+    let id = self.next_id;
+    self.next_id += 1;
+
+    self.jobs.push(Job {
+      id,
+      commands,
+      output,
+      timeout,
+      status: JobStatus::Queued,
+      started_at: None,
+      finished_at: None,
+      outputs: vec![],
+      cancelled: Arc::new(AtomicBool::new(false)),
+    });
+    self.prune();
+
+    id
+  }
+
+  // Marks as many queued jobs as there's room for as running, oldest first, and
+  // returns their ids so that they can be started.
+  pub fn start_ready(&mut self, now: Instant) -> Vec<usize> {
+    let mut room = self.max_concurrent.saturating_sub(self.running_count());
+    let mut started = vec![];
+
+    for job in self.jobs.iter_mut() {
+      if room == 0 {
+        break;
+      }
+      if job.status == JobStatus::Queued {
+        job.status = JobStatus::Running;
+        job.started_at = Some(now);
+        started.push(job.id);
+        room -= 1;
+      }
+    }
+
+    started
+  }
+
+  pub fn finish(&mut self, id: usize, outputs: Vec<CommandOutput>, now: Instant) -> Option<&Job> {
+    let job = self.jobs.iter_mut().find(|job| job.id == id)?;
+
+    job.status = if job.cancelled.load(Ordering::SeqCst) || outputs.iter().any(|o| o.cancelled) {
+      JobStatus::Cancelled
+    } else if outputs.iter().any(|o| o.timed_out_after.is_some()) {
+      JobStatus::TimedOut
+    } else if outputs.iter().all(|output| output.success()) {
+      JobStatus::Succeeded
+    } else {
+      JobStatus::Failed
+    };
+    job.finished_at = Some(now);
+    job.outputs = outputs;
+
+    Some(job)
+  }
+
+  // A queued job is cancelled on the spot. A running job is only flagged, and
+  // its commands need to be cancelled by the caller. Returns whether the job was
+  // running.
+  pub fn cancel(&mut self, id: usize, now: Instant) -> bool {
+    let job = match self.jobs.iter_mut().find(|job| job.id == id) {
+      Some(job) => job,
+      None => return false,
+    };
+
+    job.cancelled.store(true, Ordering::SeqCst);
+    match job.status {
+      JobStatus::Queued => {
+        job.status = JobStatus::Cancelled;
+        job.finished_at = Some(now);
+        false
+      }
+      JobStatus::Running => true,
+      _ => false,
+    }
+  }
+
+  pub fn cancel_all(&mut self, now: Instant) {
+    let ids = self.jobs.iter().map(|job| job.id).collect::<Vec<usize>>();
+    for id in ids {
+      self.cancel(id, now);
+    }
+  }
+
+  pub fn get(&self, id: usize) -> Option<&Job> {
+    self.jobs.iter().find(|job| job.id == id)
+  }
+
+  pub fn selected(&self) -> Option<&Job> {
+    self.jobs.get(self.table.state.selected()?)
+  }
+
+  pub fn running_count(&self) -> usize {
+    self.count(JobStatus::Running)
+  }
+
+  pub fn is_busy(&self) -> bool {
+    self.jobs.iter().any(|job| !job.status.is_finished())
+  }
+
+  // for the status bar, while there are jobs that haven't finished
+  pub fn summary(&self) -> Option<String> {
+    let running = self
+      .jobs
+      .iter()
+      .filter(|job| job.status == JobStatus::Running)
+      .collect::<Vec<&Job>>();
+    let queued = self.count(JobStatus::Queued);
+
+    let summary = match running.as_slice() {
+      [] if queued == 0 => return None,
+      [] => String::from("Waiting to run commands"),
+      [job] => match job.commands.as_slice() {
+        [command] => format!("Running command: {}", command),
+        commands => format!("Running {} commands", commands.len()),
+      },
+      jobs => format!("Running {} jobs", jobs.len()),
+    };
+
+    Some(match queued {
+      0 => summary,
+      _ => format!("{} ({} queued)", summary, queued),
+    })
+  }
+
+  fn count(&self, status: JobStatus) -> usize {
+    self.jobs.iter().filter(|job| job.status == status).count()
+  }
+
+  fn prune(&mut self) {
+    let finished = self
+      .jobs
+      .iter()
+      .filter(|job| job.status.is_finished())
+      .count();
+    let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+    if excess == 0 {
+      return;
+    }
+
+    // the jobs move up as older ones go, so the cursor has to follow its job
+    let selected = self.table.state.selected();
+    let selected_id = self.selected().map(|job| job.id);
+    self.jobs.retain(|job| {
+      if excess > 0 && job.status.is_finished() {
+        excess -= 1;
+        return false;
+      }
+      true
+    });
+
+    self.table.row_count = self.jobs.len();
+    if let Some(selected) = selected {
+      let position = selected_id.and_then(|id| self.jobs.iter().position(|job| job.id == id));
+      // if the selected job was forgotten, we stay at the same position
+      let last = self.jobs.len().checked_sub(1);
+      self
+        .table
+        .state
+        .select(position.or_else(|| last.map(|last| selected.min(last))));
+    }
+  }
+}
+
+// e.g. `0.4s`, `12.0s` or `3m05s`
+pub fn format_duration(duration: Duration) -> String {
+  let seconds = duration.as_secs();
+  if seconds < 60 {
+    format!("{:.1}s", duration.as_secs_f64())
+  } else {
+    format!("{}m{:02}s", seconds / 60, seconds % 60)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn output(command: &str, exit_code: i32) -> CommandOutput {
+    CommandOutput {
+      command: String::from(command),
+      stdout: String::from(""),
+      stderr: String::from(""),
+      exit_code: Some(exit_code),
+      cancelled: false,
+      timed_out_after: None,
+    }
+  }
+
+  fn statuses(jobs: &Jobs) -> Vec<JobStatus> {
+    jobs.jobs.iter().map(|job| job.status).collect()
+  }
+
+  #[test]
+  fn test_jobs_run_one_at_a_time_by_default() {
+    let mut jobs = Jobs::new(1);
+    let now = Instant::now();
+    let first = jobs.enqueue(vec![CommandLine::from("a")], Output::Discard, None);
+    let second = jobs.enqueue(vec![CommandLine::from("b")], Output::Discard, None);
+
+    assert_eq!(jobs.start_ready(now), vec![first]);
+    assert!(jobs.start_ready(now).is_empty());
+    assert_eq!(
+      jobs.summary(),
+      Some(String::from("Running command: a (1 queued)"))
+    );
+
+    jobs.finish(first, vec![output("a", 1)], now);
+    assert_eq!(jobs.start_ready(now), vec![second]);
+    jobs.finish(second, vec![output("b", 0)], now);
+
+    assert_eq!(
+      statuses(&jobs),
+      vec![JobStatus::Failed, JobStatus::Succeeded]
+    );
+    assert_eq!(jobs.get(first).unwrap().exit_code(), Some(1));
+    assert!(!jobs.is_busy());
+    assert_eq!(jobs.summary(), None);
+  }
+
+  #[test]
+  fn test_concurrent_jobs() {
+    let mut jobs = Jobs::new(2);
+    for command in ["a", "b", "c"] {
+      jobs.enqueue(vec![CommandLine::from(command)], Output::Discard, None);
+    }
+
+    assert_eq!(jobs.start_ready(Instant::now()), vec![0, 1]);
+    assert_eq!(
+      jobs.summary(),
+      Some(String::from("Running 2 jobs (1 queued)"))
+    );
+  }
+
+  #[test]
+  fn test_cancel() {
+    let mut jobs = Jobs::new(1);
+    let now = Instant::now();
+    let running = jobs.enqueue(vec![CommandLine::from("a")], Output::Discard, None);
+    let queued = jobs.enqueue(vec![CommandLine::from("b")], Output::Discard, None);
+    jobs.start_ready(now);
+
+    assert!(!jobs.cancel(queued, now));
+    assert!(jobs.cancel(running, now));
+    assert_eq!(
+      statuses(&jobs),
+      vec![JobStatus::Running, JobStatus::Cancelled]
+    );
+
+    // the command finished before it could be interrupted, but we still count
+    // the job as cancelled
+    jobs.finish(running, vec![output("a", 0)], now);
+    assert!(jobs.start_ready(now).is_empty());
+    assert_eq!(
+      statuses(&jobs),
+      vec![JobStatus::Cancelled, JobStatus::Cancelled]
+    );
+  }
+
+  #[test]
+  fn test_format_duration() {
+    assert_eq!(format_duration(Duration::from_millis(400)), "0.4s");
+    assert_eq!(format_duration(Duration::from_secs(185)), "3m05s");
+  }
+
+  #[test]
+  fn test_old_jobs_are_forgotten() {
+    let mut jobs = Jobs::new(1);
+    let now = Instant::now();
+    for _ in 0..MAX_FINISHED_JOBS + 5 {
+      let id = jobs.enqueue(vec![CommandLine::from("a")], Output::Discard, None);
+      jobs.start_ready(now);
+      jobs.finish(id, vec![output("a", 0)], now);
+    }
+
+    assert_eq!(jobs.jobs.len(), MAX_FINISHED_JOBS + 1);
+    assert_eq!(jobs.jobs[0].id, 4);
+  }
+
+  #[test]
+  fn test_selection_follows_its_job_when_old_jobs_are_forgotten() {
+    let mut jobs = Jobs::new(1);
+    let now = Instant::now();
+    let run = |jobs: &mut Jobs| {
+      let id = jobs.enqueue(vec![CommandLine::from("a")], Output::Discard, None);
+      jobs.start_ready(now);
+      jobs.finish(id, vec![output("a", 0)], now);
+    };
+    for _ in 0..MAX_FINISHED_JOBS {
+      run(&mut jobs);
+    }
+    jobs.table.state.select(Some(10));
+
+    // finished jobs are only forgotten when another is queued, so this forgets one
+    run(&mut jobs);
+    run(&mut jobs);
+    assert_eq!(jobs.table.state.selected(), Some(9));
+    assert_eq!(jobs.selected().unwrap().id, 10);
+  }
+}
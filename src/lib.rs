@@ -0,0 +1,16 @@
+mod ansi;
+pub mod app;
+pub mod args;
+pub mod command;
+pub mod config;
+pub mod event_loop;
+mod jobs;
+mod os_commands;
+mod output_view;
+pub mod parse;
+mod preview_cache;
+mod stateful_table;
+mod stream_buffer;
+mod template;
+mod terminal_manager;
+mod ui;
@@ -2,21 +2,7 @@ use std::env;
 #[allow(dead_code)]
 use std::error::Error;
 
-mod app;
-mod args;
-mod command;
-mod config;
-mod event_loop;
-mod os_commands;
-mod parse;
-mod stateful_table;
-mod template;
-mod terminal_manager;
-mod ui;
-
-use app::App;
-use args::Args;
-use config::storage;
+use lazycli::{app::App, args::Args, command, config::storage, event_loop};
 use storage::CONFIG_DIR_ENV_VAR;
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -24,9 +10,26 @@ fn main() -> Result<(), Box<dyn Error>> {
   let config_path = storage::config_path(env::var(CONFIG_DIR_ENV_VAR).ok())?;
   let config = storage::prepare_config(&config_path)?;
 
+  if let Err(error) = config.validate() {
+    eprintln!("{} in {}", error, config_path.display());
+    std::process::exit(1);
+  }
+
+  if let Some(name) = &args.profile {
+    if config.find_profile_by_name(name).is_none() {
+      eprintln!("No profile named `{}` in {}", name, config_path.display());
+      std::process::exit(1);
+    }
+  }
+
   let app = App::new(&config, config_path, args);
+  if let Some(shell) = app.shell() {
+    command::set_shell(shell);
+  }
 
-  event_loop::run(app)?;
+  let result = event_loop::run(app);
+  command::kill_running();
+  result?;
 
   Ok(())
 }
@@ -0,0 +1,235 @@
+use crate::command::CommandOutput;
+
+// The output of the commands run by a keybinding, for showing in a popup that
+// can be scrolled and searched.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OutputView {
+  pub lines: Vec<String>,
+  // the index of the line at the top of the popup
+  pub scroll: usize,
+  // how many lines fit in the popup, as of the last draw
+  pub height: usize,
+  // the search being typed, if there is one
+  pub search: Option<String>,
+  // the search that was last entered, which matching lines are highlighted for
+  pub query: String,
+}
+
+impl OutputView {
+  // Each command gets a heading with its exit code, followed by its stdout and
+  // then its stderr.
+  pub fn new(outputs: &[CommandOutput]) -> OutputView {
+    let mut lines = vec![];
+    for output in outputs {
+      let exit_code = match output.exit_code {
+        _ if output.cancelled => String::from("cancelled"),
+        _ if output.timed_out_after.is_some() => {
+          format!("timed out after {:?}", output.timed_out_after.unwrap())
+        }
+        Some(code) => format!("exit code {}", code),
+        None => String::from("killed by a signal"),
+      };
+      lines.push(format!("$ {} ({})", output.command, exit_code));
+      lines.extend(output.stdout.lines().map(String::from));
+      if !output.stderr.is_empty() {
+        lines.push(String::from("stderr:"));
+        lines.extend(output.stderr.lines().map(String::from));
+      }
+      lines.push(String::from(""));
+    }
+    lines.pop();
+
+    OutputView {
+      lines,
+      scroll: 0,
+      height: 0,
+      search: None,
+      query: String::from(""),
+    }
+  }
+
+  fn max_scroll(&self) -> usize {
+    self.lines.len().saturating_sub(self.height.max(1))
+  }
+
+  pub fn scroll_down(&mut self, lines: usize) {
+    self.scroll = (self.scroll + lines).min(self.max_scroll());
+  }
+
+  pub fn scroll_up(&mut self, lines: usize) {
+    self.scroll = self.scroll.saturating_sub(lines);
+  }
+
+  pub fn scroll_to_top(&mut self) {
+    self.scroll = 0;
+  }
+
+  pub fn scroll_to_bottom(&mut self) {
+    self.scroll = self.max_scroll();
+  }
+
+  pub fn page_size(&self) -> usize {
+    self.height.max(2) - 1
+  }
+
+  pub fn is_match(&self, line: &str) -> bool {
+    !self.query.is_empty() && line.to_lowercase().contains(&self.query.to_lowercase())
+  }
+
+  pub fn match_indices(&self) -> Vec<usize> {
+    (0..self.lines.len())
+      .filter(|index| self.is_match(&self.lines[*index]))
+      .collect()
+  }
+
+  pub fn start_search(&mut self) {
+    self.search = Some(String::from(""));
+  }
+
+  pub fn push_search_char(&mut self, c: char) {
+    if let Some(search) = self.search.as_mut() {
+      search.push(c);
+    }
+  }
+
+  pub fn pop_search_char(&mut self) {
+    if let Some(search) = self.search.as_mut() {
+      search.pop();
+    }
+  }
+
+  pub fn cancel_search(&mut self) {
+    self.search = None;
+  }
+
+  // jumps to the first match from the top of the popup down
+  pub fn finish_search(&mut self) {
+    if let Some(search) = self.search.take() {
+      self.query = search;
+      self.jump_to_match(|index, scroll| index >= scroll, false);
+    }
+  }
+
+  pub fn next_match(&mut self) {
+    self.jump_to_match(|index, scroll| index > scroll, false);
+  }
+
+  pub fn previous_match(&mut self) {
+    self.jump_to_match(|index, scroll| index < scroll, true);
+  }
+
+  // Scrolls the first (or last) match satisfying the condition to the top (or as
+  // near as we can get without scrolling past the end), wrapping around if there
+  // isn't one.
+  fn jump_to_match<F: Fn(usize, usize) -> bool>(&mut self, condition: F, backwards: bool) {
+    let mut matches = self.match_indices();
+    if backwards {
+      matches.reverse();
+    }
+
+    let target = matches
+      .iter()
+      .find(|index| condition(**index, self.scroll))
+      .or_else(|| matches.first());
+    if let Some(target) = target {
+      self.scroll = (*target).min(self.max_scroll());
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::time::Duration;
+
+  fn output(command: &str, stdout: &str, stderr: &str, exit_code: i32) -> CommandOutput {
+    CommandOutput {
+      command: String::from(command),
+      stdout: String::from(stdout),
+      stderr: String::from(stderr),
+      exit_code: Some(exit_code),
+      cancelled: false,
+      timed_out_after: None,
+    }
+  }
+
+  #[test]
+  fn test_new() {
+    let view = OutputView::new(&[
+      output("du -sh src", "48K\tsrc\n", "", 0),
+      output("du -sh nope", "", "du: nope: No such file\n", 1),
+    ]);
+
+    assert_eq!(
+      view.lines,
+      vec![
+        "$ du -sh src (exit code 0)",
+        "48K\tsrc",
+        "",
+        "$ du -sh nope (exit code 1)",
+        "stderr:",
+        "du: nope: No such file",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_new_with_timeout() {
+    let view = OutputView::new(&[CommandOutput {
+      timed_out_after: Some(Duration::from_secs(5)),
+      exit_code: None,
+      ..output("sleep 10", "", "", 0)
+    }]);
+
+    assert_eq!(view.lines, vec!["$ sleep 10 (timed out after 5s)"]);
+  }
+
+  #[test]
+  fn test_scrolling_stops_at_the_end() {
+    let mut view = OutputView::new(&[output("seq 10", "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n", "", 0)]);
+    view.height = 4;
+
+    view.scroll_down(100);
+    assert_eq!(view.scroll, 7);
+    view.scroll_up(2);
+    assert_eq!(view.scroll, 5);
+    view.scroll_up(100);
+    assert_eq!(view.scroll, 0);
+  }
+
+  #[test]
+  fn test_search() {
+    let mut view = OutputView::new(&[output("log", "Error: a\nok\nerror: b\nok\n", "", 0)]);
+    view.height = 2;
+
+    view.start_search();
+    for c in "ERROR".chars() {
+      view.push_search_char(c);
+    }
+    view.finish_search();
+    assert_eq!(view.scroll, 1);
+
+    view.next_match();
+    assert_eq!(view.scroll, 3);
+    // wraps back around to the first match
+    view.next_match();
+    assert_eq!(view.scroll, 1);
+    view.previous_match();
+    assert_eq!(view.scroll, 3);
+  }
+
+  #[test]
+  fn test_match_near_the_end_does_not_scroll_past_it() {
+    let mut view = OutputView::new(&[output("log", "a\nb\nc\nerror\nd\n", "", 0)]);
+    view.height = 3;
+
+    view.start_search();
+    for c in "err".chars() {
+      view.push_search_char(c);
+    }
+    view.finish_search();
+    // `error` is the fifth line, after the heading, but the last three lines fit
+    assert_eq!(view.scroll, 3);
+  }
+}
@@ -1,15 +1,18 @@
 use std::str::Chars;
+use unicode_width::UnicodeWidthChar;
 
 // going my_string.char_indices actually returns an iterator where the index
 // is the byte offset rather than the actual index of the char. So we've got our
-// custom CharPosIter struct here to get the behaviour we want.
+// custom CharPosIter struct here to get the behaviour we want. The position is
+// the terminal column the char starts at, so a wide char like `日` takes up two
+// columns and a combining accent takes up none.
 pub struct CharPosIter<'a> {
   s: Chars<'a>,
   index: usize,
 }
 
 impl<'a> CharPosIter<'a> {
-  pub fn new(s: &'a str) -> CharPosIter {
+  pub fn new(s: &'a str) -> CharPosIter<'a> {
     CharPosIter {
       s: s.chars(),
       index: 0,
@@ -18,14 +21,17 @@ impl<'a> CharPosIter<'a> {
 }
 
 impl<'a> Iterator for CharPosIter<'a> {
-  type Item = (usize, char);
+  // the char's column, the char, and how many columns it takes up
+  type Item = (usize, char, usize);
 
-  fn next(&mut self) -> Option<(usize, char)> {
+  fn next(&mut self) -> Option<(usize, char, usize)> {
     let val = self.s.next()?;
+    // control chars like tabs have no defined width, so we count them as one column
+    let width = val.width().unwrap_or(1);
 
-    let result = Some((self.index, val));
+    let result = Some((self.index, val, width));
 
-    self.index += 1;
+    self.index += width;
     result
   }
 }
@@ -0,0 +1,209 @@
+use super::char_pos_iter::CharPosIter;
+
+// A terminal column that some lines have text in can still be a gap between
+// columns, as long as only a few lines (fewer than one in this many) have text
+// there, and it's text that has overflowed from a column further left, like a
+// long IPv6 address in `netstat` output. Header text is never treated that way.
+const OUTLIER_RATIO: usize = 5;
+
+// Works out the terminal column where each column of the table starts. `header`
+// is true if the first line holds the column names.
+//
+// A column starts after a gap that runs down the whole output. Not every gap is
+// a column though: values can contain spaces (`2 months ago`, `Mounted on`), so
+// a gap only counts if there's some evidence for it:
+// * it has to be backed by more than one line (unless there's only one line),
+// * and if there's a header, a header word has to start or end with the column
+//   or the column has to be made up of right-aligned numbers.
+pub fn get_column_indices(lines: &[&str], header: bool) -> Vec<usize> {
+  let grid = lines
+    .iter()
+    .map(|line| to_grid(line))
+    .collect::<Vec<Vec<char>>>();
+  let width = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+
+  // how many lines have text in each column, and how many are long enough to reach it
+  let mut occupied = vec![0; width];
+  let mut reach = vec![0; width];
+  for row in grid.iter() {
+    for (column, c) in row.iter().enumerate() {
+      reach[column] += 1;
+      if *c != ' ' {
+        occupied[column] += 1;
+      }
+    }
+  }
+
+  let is_minority = |count: usize, column: usize| count * OUTLIER_RATIO < reach[column];
+
+  // how many lines have text in each column that carries on from a word which
+  // started in a column most lines have text in
+  let mut overflowing = vec![0; width];
+  for row in grid.iter().skip(if header { 1 } else { 0 }) {
+    let mut word_start = 0;
+    for (column, c) in row.iter().enumerate() {
+      if *c == ' ' {
+        continue;
+      }
+      if column == 0 || row[column - 1] == ' ' {
+        word_start = column;
+      } else if !is_minority(occupied[word_start], word_start) {
+        overflowing[column] += 1;
+      }
+    }
+  }
+
+  let is_gap = |column: usize| {
+    occupied[column] == overflowing[column] && is_minority(overflowing[column], column)
+  };
+
+  let block_starts = (0..width)
+    .filter(|&column| !is_gap(column) && (column == 0 || is_gap(column - 1)))
+    .collect::<Vec<usize>>();
+
+  let min_reach = lines.len().min(2);
+  let header = header && !grid.is_empty();
+  let data_rows = if header { &grid[1..] } else { &grid[..] };
+
+  let mut result = vec![0];
+  for &start in block_starts.iter().skip(1) {
+    let end = (start..width)
+      .find(|&column| is_gap(column))
+      .unwrap_or(width);
+
+    if reach[start] < min_reach {
+      continue;
+    }
+
+    if header
+      && !header_supports(&grid[0], start, end)
+      && !is_right_aligned_numbers(data_rows, start, end)
+    {
+      continue;
+    }
+
+    result.push(start);
+  }
+
+  result
+}
+
+// Whether there's a gap just before each column in the line, so that splitting
+// it into the columns won't cut a value in two.
+pub fn fits(line: &str, column_indices: &[usize]) -> bool {
+  let grid = to_grid(line);
+  column_indices
+    .iter()
+    .filter(|&&start| start > 0)
+    .all(|&start| grid.get(start - 1).is_none_or(|c| *c == ' '))
+}
+
+// each char of the line at the terminal column(s) it takes up, without the trailing spaces
+fn to_grid(line: &str) -> Vec<char> {
+  let line = line.trim_end();
+  if line.bytes().all(|b| b == b' ' || b.is_ascii_graphic()) {
+    return line.chars().collect();
+  }
+
+  let mut grid = vec![];
+  for (index, c, width) in CharPosIter::new(line) {
+    grid.resize(index, ' ');
+    grid.extend(std::iter::repeat_n(c, width));
+  }
+
+  // The spaces in a tree's branches at the start of a line (`│   ├── src`)
+  // belong to the first column rather than separating it from the next.
+  let is_box_drawing = |c: &char| ('\u{2500}'..='\u{257f}').contains(c);
+  let branches_len = grid
+    .iter()
+    .take_while(|c| **c == ' ' || is_box_drawing(c))
+    .count();
+  if grid[..branches_len].iter().any(is_box_drawing) {
+    for c in grid[..branches_len].iter_mut() {
+      *c = '─';
+    }
+  }
+
+  grid
+}
+
+fn header_supports(header: &[char], start: usize, end: usize) -> bool {
+  let is_text = |column: usize| header.get(column).is_some_and(|c| *c != ' ');
+
+  let word_starts_here = is_text(start) && (start == 0 || !is_text(start - 1));
+  let word_ends_here = is_text(end - 1) && !is_text(end);
+
+  word_starts_here || word_ends_here
+}
+
+fn is_right_aligned_numbers(rows: &[Vec<char>], start: usize, end: usize) -> bool {
+  let values = rows
+    .iter()
+    .filter(|row| row.len() > start)
+    .map(|row| &row[start..end.min(row.len())])
+    .filter(|value| value.iter().any(|c| *c != ' '))
+    .collect::<Vec<&[char]>>();
+
+  !values.is_empty()
+    && values.iter().all(|value| {
+      value.len() == end - start
+        && value[value.len() - 1] != ' '
+        && is_numeric(value.iter().collect::<String>().trim())
+    })
+}
+
+// e.g. `42`, `-1`, `0.5`, `26%`, `15:19`, `1,024`, `4.0K` or `72.8MB`
+pub fn is_numeric(value: &str) -> bool {
+  let without_unit = value.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+
+  without_unit.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+')
+    && without_unit.chars().any(|c| c.is_ascii_digit())
+    && without_unit
+      .chars()
+      .all(|c| c.is_ascii_digit() || ".,:%+-".contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_is_numeric() {
+    for value in ["42", "-1", "0.5", "26%", "15:19", "1,024", "4.0K", "72.8MB"] {
+      assert!(is_numeric(value), "`{}` should be numeric", value);
+    }
+    for value in ["", "Jan", "-rw-r--r--", "1K-blocks", "v1.2", "?"] {
+      assert!(!is_numeric(value), "`{}` shouldn't be numeric", value);
+    }
+  }
+
+  #[test]
+  fn test_header_word_with_space() {
+    let lines = [
+      "Filesystem      Size  Mounted on",
+      "/dev/vda        252G  /",
+      "tmpfs           3.0G  /dev",
+    ];
+
+    assert_eq!(get_column_indices(&lines, true), vec![0, 16, 22]);
+  }
+
+  #[test]
+  fn test_fits() {
+    let column_indices = [0, 8, 13];
+
+    assert!(fits("nginx   Up   80/tcp", &column_indices));
+    assert!(fits("nginx", &column_indices));
+    assert!(!fits("postgresql Up 5432/tcp", &column_indices));
+  }
+
+  #[test]
+  fn test_overflowing_outlier() {
+    let mut lines = vec!["Local Address           State"];
+    lines.extend(std::iter::repeat_n("127.0.0.1:53            LISTEN", 4));
+    lines.push("fe80::1ff:fe23:4567:890a:22 LISTEN");
+
+    assert_eq!(get_column_indices(&lines, true), vec![0, 24]);
+  }
+}
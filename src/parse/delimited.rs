@@ -0,0 +1,172 @@
+use regex::Regex;
+
+use super::Row;
+use crate::config::Delimiter;
+
+// Splits each line on the delimiter. Blank lines are skipped.
+pub fn parse(text: &str, delimiter: &Delimiter) -> Result<Vec<Row>, String> {
+  match delimiter {
+    Delimiter::Csv => parse_csv(text),
+    Delimiter::Tsv => Ok(split_lines(text, |line| split_on_char(line, '\t'))),
+    Delimiter::Char(c) => Ok(split_lines(text, |line| split_on_char(line, *c))),
+    Delimiter::Text(delimiter) => Ok(split_lines(text, |line| {
+      line
+        .split(delimiter.as_str())
+        .map(|cell| cell.to_owned())
+        .collect()
+    })),
+    Delimiter::Regex(regex) => {
+      let regex = Regex::new(regex).map_err(|e| e.to_string())?;
+      Ok(split_lines(text, |line| {
+        regex.split(line).map(|cell| cell.to_owned()).collect()
+      }))
+    }
+  }
+}
+
+fn split_on_char(line: &str, c: char) -> Vec<String> {
+  line.split(c).map(|cell| cell.to_owned()).collect()
+}
+
+fn split_lines<F>(text: &str, split: F) -> Vec<Row>
+where
+  F: Fn(&str) -> Vec<String>,
+{
+  text
+    .lines()
+    .filter(|line| !line.trim().is_empty())
+    .map(|line| Row::new(line.to_owned(), split(line)))
+    .collect()
+}
+
+// A quoted value can span several lines, in which case the row's original line
+// is the whole record.
+fn parse_csv(text: &str) -> Result<Vec<Row>, String> {
+  let mut reader = csv::ReaderBuilder::new()
+    .has_headers(false)
+    .flexible(true)
+    .from_reader(text.as_bytes());
+
+  let mut rows = vec![];
+  let mut record = csv::StringRecord::new();
+  let mut start = 0;
+  loop {
+    let more = reader
+      .read_record(&mut record)
+      .map_err(|error| format!("Failed to parse CSV: {}", error))?;
+    if !more {
+      break;
+    }
+
+    let end = reader.position().byte() as usize;
+    // blank lines are skipped by the reader and so end up in the following record's span
+    let original_line = text[start..end].trim_matches(&['\r', '\n'][..]).to_owned();
+    start = end;
+
+    rows.push(Row::new(
+      original_line,
+      record.iter().map(|cell| cell.to_owned()).collect(),
+    ));
+  }
+
+  Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn cells(rows: Vec<Row>) -> Vec<Vec<String>> {
+    rows.into_iter().map(|row| row.cells).collect()
+  }
+
+  fn strings(strs: &[&str]) -> Vec<String> {
+    strs.iter().map(|s| String::from(*s)).collect()
+  }
+
+  #[test]
+  fn test_csv_quoting() {
+    let text = "name,description\nfoo,\"has, a comma\"\nbar,\"has \"\"quotes\"\"\"\nbaz,\n";
+
+    assert_eq!(
+      cells(parse(text, &Delimiter::Csv).unwrap()),
+      vec![
+        strings(&["name", "description"]),
+        strings(&["foo", "has, a comma"]),
+        strings(&["bar", "has \"quotes\""]),
+        strings(&["baz", ""]),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_csv_multiline_value() {
+    let rows = parse("a,\"line 1\nline 2\"\nb,c", &Delimiter::Csv).unwrap();
+
+    assert_eq!(
+      rows,
+      vec![
+        Row::new(
+          String::from("a,\"line 1\nline 2\""),
+          strings(&["a", "line 1\nline 2"])
+        ),
+        Row::new(String::from("b,c"), strings(&["b", "c"])),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_tsv_keeps_empty_fields_and_spaces() {
+    let text = "refs/heads/main\tFix the thing\nrefs/heads/wip\t\n";
+
+    assert_eq!(
+      cells(parse(text, &Delimiter::Tsv).unwrap()),
+      vec![
+        strings(&["refs/heads/main", "Fix the thing"]),
+        strings(&["refs/heads/wip", ""]),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_char() {
+    let text = "root:x:0:0:root:/root:/bin/bash\nnobody:x:65534:65534:nobody:/nonexistent:/usr/sbin/nologin\n";
+
+    assert_eq!(
+      cells(parse(text, &Delimiter::Char(':')).unwrap()),
+      vec![
+        strings(&["root", "x", "0", "0", "root", "/root", "/bin/bash"]),
+        strings(&[
+          "nobody",
+          "x",
+          "65534",
+          "65534",
+          "nobody",
+          "/nonexistent",
+          "/usr/sbin/nologin"
+        ]),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_text() {
+    let delimiter = Delimiter::Text(String::from("||"));
+
+    assert_eq!(
+      cells(parse("a||b c||\n|d||e\n", &delimiter).unwrap()),
+      vec![strings(&["a", "b c", ""]), strings(&["|d", "e"])]
+    );
+  }
+
+  #[test]
+  fn test_regex() {
+    let delimiter = Delimiter::Regex(String::from(r"\s*\|\s*"));
+
+    assert_eq!(
+      cells(parse("a | b c |d\n\n", &delimiter).unwrap()),
+      vec![strings(&["a", "b c", "d"])]
+    );
+  }
+}
@@ -0,0 +1,201 @@
+use serde_json::{Deserializer, Value};
+
+use super::{Parsed, Row};
+
+// Accepts either a JSON array (e.g. `gh api ...`) or a stream of JSON values, one
+// per line (e.g. `docker ps --format '{{json .}}'`). If `columns` is empty, we
+// derive the columns from the keys of the objects in the order we first see them.
+pub fn parse(text: &str, columns: &[String]) -> Result<Parsed, String> {
+  let values = values_from_text(text)?;
+
+  let column_names = if columns.is_empty() {
+    derive_column_names(&values)
+  } else {
+    columns.to_vec()
+  };
+
+  let rows = values
+    .into_iter()
+    .map(|value| {
+      let cells = match value {
+        Value::Object(_) => column_names
+          .iter()
+          .map(|column| lookup(&value, column).map(to_cell).unwrap_or_default())
+          .collect(),
+        _ => vec![to_cell(&value)],
+      };
+
+      Row {
+        original_line: value.to_string(),
+        cells,
+        json: Some(value),
+        ..Default::default()
+      }
+    })
+    .collect();
+
+  Ok(Parsed {
+    column_names,
+    rows,
+    ..Default::default()
+  })
+}
+
+fn values_from_text(text: &str) -> Result<Vec<Value>, String> {
+  let values = Deserializer::from_str(text)
+    .into_iter::<Value>()
+    .collect::<Result<Vec<Value>, _>>()
+    .map_err(|e| format!("Failed to parse output as JSON: {}", e))?;
+
+  if values.len() != 1 {
+    return Ok(values);
+  }
+
+  match values.into_iter().next().unwrap() {
+    Value::Array(values) => Ok(values),
+    Value::Object(mut object) => match object.get_mut("items") {
+      // kubernetes wraps its lists in an object with an `items` key
+      Some(Value::Array(items)) => Ok(std::mem::take(items)),
+      _ => Ok(vec![Value::Object(object)]),
+    },
+    value => Ok(vec![value]),
+  }
+}
+
+fn derive_column_names(values: &[Value]) -> Vec<String> {
+  let mut column_names: Vec<String> = vec![];
+
+  for value in values {
+    if let Value::Object(object) = value {
+      for key in object.keys() {
+        if !column_names.contains(key) {
+          column_names.push(key.clone());
+        }
+      }
+    }
+  }
+
+  column_names
+}
+
+// resolves a dot-separated path like `metadata.name` or `containers.0.image`. An
+// empty path gives back the value itself.
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+  if path.is_empty() {
+    return Some(value);
+  }
+
+  path.split('.').try_fold(value, |value, key| match value {
+    Value::Object(object) => object.get(key),
+    Value::Array(array) => array.get(key.parse::<usize>().ok()?),
+    _ => None,
+  })
+}
+
+// strings are shown without their quotes and null is shown as nothing. Anything
+// nested is shown as compact JSON.
+pub fn to_cell(value: &Value) -> String {
+  match value {
+    Value::Null => String::from(""),
+    Value::String(s) => s.clone(),
+    value => value.to_string(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use serde_json::json;
+
+  fn strings(strs: &[&str]) -> Vec<String> {
+    strs.iter().map(|s| String::from(*s)).collect()
+  }
+
+  #[test]
+  fn test_parse_array() {
+    let text = r#"[{"name": "a", "size": 1}, {"name": "b", "size": 2, "extra": null}]"#;
+
+    let parsed = parse(text, &[]).unwrap();
+
+    assert_eq!(parsed.column_names, strings(&["name", "size", "extra"]));
+    assert_eq!(
+      parsed.rows,
+      vec![
+        Row {
+          original_line: String::from(r#"{"name":"a","size":1}"#),
+          cells: strings(&["a", "1", ""]),
+          json: Some(json!({"name": "a", "size": 1})),
+          ..Default::default()
+        },
+        Row {
+          original_line: String::from(r#"{"name":"b","size":2,"extra":null}"#),
+          cells: strings(&["b", "2", ""]),
+          json: Some(json!({"name": "b", "size": 2, "extra": null})),
+          ..Default::default()
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_json_lines() {
+    let text = "{\"ID\":\"17c523089229\",\"Names\":\"blah\"}\n\
+                {\"ID\":\"dcddf219bb2b\",\"Names\":\"blah-sidekiq_2\"}\n";
+
+    let parsed = parse(text, &[]).unwrap();
+
+    assert_eq!(parsed.column_names, strings(&["ID", "Names"]));
+    assert_eq!(
+      parsed
+        .rows
+        .iter()
+        .map(|row| row.cells.clone())
+        .collect::<Vec<_>>(),
+      vec![
+        strings(&["17c523089229", "blah"]),
+        strings(&["dcddf219bb2b", "blah-sidekiq_2"]),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_with_columns() {
+    let text =
+      r#"{"items": [{"metadata": {"name": "web"}, "spec": {"containers": [{"image": "nginx"}]}}]}"#;
+
+    let parsed = parse(
+      text,
+      &strings(&["metadata.name", "spec.containers.0.image"]),
+    )
+    .unwrap();
+
+    assert_eq!(
+      parsed.column_names,
+      strings(&["metadata.name", "spec.containers.0.image"])
+    );
+    assert_eq!(parsed.rows[0].cells, strings(&["web", "nginx"]));
+  }
+
+  #[test]
+  fn test_parse_nested_values_as_json() {
+    let text = r#"[{"labels": {"app": "web"}, "ports": [80, 443]}]"#;
+
+    let parsed = parse(text, &[]).unwrap();
+
+    assert_eq!(
+      parsed.rows[0].cells,
+      strings(&[r#"{"app":"web"}"#, "[80,443]"])
+    );
+  }
+
+  #[test]
+  fn test_parse_invalid_json() {
+    let result = parse("{\"name\": ", &[]);
+
+    assert!(matches!(
+      result,
+      Err(e) if e.starts_with("Failed to parse output as JSON"),
+    ));
+  }
+}
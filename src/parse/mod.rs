@@ -1,16 +1,43 @@
 mod char_pos_iter;
+mod columns;
+mod delimited;
+pub mod json;
+mod records;
+mod row_regex;
+mod tree;
 
-use char_pos_iter::CharPosIter;
 use itertools::Itertools;
+use regex::Regex;
 use std::{
-  collections::HashSet,
-  iter::{once, FromIterator},
+  cmp,
+  collections::HashMap,
+  iter::{once, Peekable},
+  str::Chars,
 };
+use tui::text::{Span, Spans};
+use unicode_width::UnicodeWidthChar;
 
-#[derive(PartialEq, Debug)]
+use crate::{
+  ansi,
+  config::{Delimiter, Format, RecordSeparator},
+};
+
+#[derive(PartialEq, Debug, Default)]
 pub struct Row {
   pub original_line: String,
   pub cells: Vec<String>,
+  // only present when parsing JSON, so that templates can reach into fields
+  // that aren't shown as columns
+  pub json: Option<serde_json::Value>,
+  // shown for context, but the cursor skips over it and keybindings can't target it
+  pub unselectable: bool,
+  // the cells with the colours from the command's output. Empty if it had none,
+  // in which case the plain cells are shown.
+  pub styled_cells: Vec<Spans<'static>>,
+  // how deeply nested the row is, in tree mode
+  pub depth: usize,
+  // the cells of the row this one is nested under, in tree mode
+  pub parent_cells: Vec<String>,
 }
 
 impl Row {
@@ -18,6 +45,7 @@ impl Row {
     Row {
       original_line,
       cells,
+      ..Default::default()
     }
   }
 
@@ -26,72 +54,411 @@ impl Row {
   }
 }
 
-pub fn parse(text: String) -> Vec<Row> {
-  let column_sizes = get_column_indices(&text)
-    .into_iter()
-    .tuple_windows()
-    .map(|(i0, i1)| i1 - i0)
-    .chain(once(usize::MAX))
-    .collect::<Vec<_>>();
+#[derive(PartialEq, Debug, Default)]
+pub struct Parsed {
+  // empty when the output doesn't tell us what the columns are called
+  pub column_names: Vec<String>,
+  pub rows: Vec<Row>,
+  // the column showing how rows nest, in tree mode
+  pub tree_column: Option<usize>,
+  // where each column starts, for whitespace-aligned tables
+  pub column_indices: Vec<usize>,
+}
 
-  text
-    .lines()
-    .map(|line| {
-      // I want to get the chars as an array, then slice that up.
-      let cells = column_sizes
-        .iter()
-        .scan(line.chars(), |chars, column_size| {
-          Some(
-            chars
-              .take(*column_size)
-              .collect::<String>()
-              .trim_end()
-              .to_owned(),
-          )
-        })
-        .collect();
-
-      Row::new(line.to_owned(), cells)
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptions {
+  pub lines_to_skip: usize,
+  pub skip_trailing_lines: usize,
+  pub ignore_lines_matching: Vec<String>,
+  pub unselectable_lines_matching: Vec<String>,
+  pub format: Format,
+  pub delimiter: Option<Delimiter>,
+  pub header: bool,
+  pub columns: Vec<String>,
+  pub row_regex: Option<String>,
+  pub show_unmatched_lines: bool,
+  pub record_separator: Option<RecordSeparator>,
+  pub tree: bool,
+  // where each column starts, if it's already been worked out from earlier lines
+  pub column_indices: Vec<usize>,
+}
+
+// Colour codes are stripped before parsing, so that everything else (column
+// detection, filtering, placeholders) deals in plain text, and are put back onto
+// the cells afterwards for display.
+pub fn parse(text: String, options: &ParseOptions) -> Result<Parsed, String> {
+  if !text.contains('\x1b') {
+    return parse_plain(text, options);
+  }
+
+  let styled_lines = ansi::parse_lines(&text);
+  let plain_text = styled_lines
+    .iter()
+    .map(|spans| ansi::plain(spans))
+    .collect::<Vec<String>>()
+    .join("\n");
+
+  let mut parsed = parse_plain(plain_text, options)?;
+
+  let styles_by_line = styled_lines
+    .iter()
+    .filter(|spans| ansi::is_styled(spans))
+    .map(|spans| (ansi::plain(spans), spans))
+    .collect::<HashMap<String, &Vec<Span<'static>>>>();
+  for row in parsed.rows.iter_mut() {
+    if let Some(spans) = styles_by_line.get(&row.original_line) {
+      row.styled_cells = style_cells(&row.original_line, &row.cells, spans);
+    }
+  }
+
+  Ok(parsed)
+}
+
+// Parses lines streamed in after the ones we've already parsed, without going
+// over those again, so that a long stream doesn't get slower with every batch.
+// Returns None if the new lines can't be parsed on their own and everything needs
+// reparsing: either a line doesn't fit the columns worked out so far, or rows
+// depend on more than their own line (records, trees, CSV's quoted newlines and
+// JSON's columns).
+pub fn parse_more(
+  lines: &[String],
+  options: &ParseOptions,
+  column_indices: &[usize],
+) -> Result<Option<Vec<Row>>, String> {
+  let line_by_line = options.format == Format::Table
+    && options.record_separator.is_none()
+    && !options.tree
+    && options.skip_trailing_lines == 0
+    && options.delimiter != Some(Delimiter::Csv);
+  if !line_by_line {
+    return Ok(None);
+  }
+
+  let text = lines.join("\n");
+  let is_aligned = options.delimiter.is_none() && options.row_regex.is_none();
+  if is_aligned && !fits_columns(&text, options, column_indices)? {
+    return Ok(None);
+  }
+
+  let parsed = parse(
+    text,
+    &ParseOptions {
+      lines_to_skip: 0,
+      header: false,
+      column_indices: column_indices.to_vec(),
+      ..options.clone()
+    },
+  )?;
+
+  Ok(Some(parsed.rows))
+}
+
+// ignored and unselectable lines aren't split into columns, so they always fit
+fn fits_columns(
+  text: &str,
+  options: &ParseOptions,
+  column_indices: &[usize],
+) -> Result<bool, String> {
+  let plain_text = if text.contains('\x1b') {
+    ansi::parse_lines(text)
+      .iter()
+      .map(|spans| ansi::plain(spans))
+      .join("\n")
+  } else {
+    text.to_owned()
+  };
+
+  let mut unsplit_regexes =
+    compile_regexes(&options.ignore_lines_matching, "ignore_lines_matching")?;
+  unsplit_regexes.extend(compile_regexes(
+    &options.unselectable_lines_matching,
+    "unselectable_lines_matching",
+  )?);
+
+  Ok(
+    plain_text
+      .lines()
+      .filter(|line| !unsplit_regexes.iter().any(|regex| regex.is_match(line)))
+      .all(|line| columns::fits(line, column_indices)),
+  )
+}
+
+// Cells appear in their line in order, so we find each one after the last. Any
+// we can't find (e.g. a CSV value that was quoted) are left unstyled.
+fn style_cells(line: &str, cells: &[String], spans: &[Span<'static>]) -> Vec<Spans<'static>> {
+  let mut position = 0;
+  cells
+    .iter()
+    .map(|cell| match line[position..].find(cell.as_str()) {
+      Some(offset) if !cell.is_empty() => {
+        let start = position + offset;
+        position = start + cell.len();
+        ansi::slice(spans, start..position)
+      }
+      _ => Spans::from(cell.clone()),
     })
     .collect()
 }
 
-fn get_column_indices(text: &String) -> Vec<usize> {
-  let mut lines = text.trim_end().lines();
+fn parse_plain(text: String, options: &ParseOptions) -> Result<Parsed, String> {
+  let ignore_regexes = compile_regexes(&options.ignore_lines_matching, "ignore_lines_matching")?;
+  let unselectable_regexes = compile_regexes(
+    &options.unselectable_lines_matching,
+    "unselectable_lines_matching",
+  )?;
+
+  let lines = text
+    .lines()
+    .skip(options.lines_to_skip)
+    .collect::<Vec<&str>>();
+  let lines = lines[..lines.len().saturating_sub(options.skip_trailing_lines)]
+    .iter()
+    .filter(|line| !ignore_regexes.iter().any(|regex| regex.is_match(line)))
+    .collect::<Vec<&&str>>();
+
+  // the header can't be unselectable
+  let is_unselectable = |index: usize, line: &str| {
+    !(index == 0 && options.header)
+      && options.format == Format::Table
+      && unselectable_regexes
+        .iter()
+        .any(|regex| regex.is_match(line))
+  };
+  let trimmed_text = lines
+    .iter()
+    .enumerate()
+    .filter(|(index, line)| !is_unselectable(*index, line))
+    .map(|(_index, line)| **line)
+    .collect::<Vec<&str>>()
+    .join("\n");
 
-  let first_line = lines.next().unwrap_or_default();
+  let mut parsed = parse_rows(trimmed_text, options)?;
 
-  let spaces_iter = CharPosIter::new(first_line)
-    // ignoring index 0 for the sake of something like git status --short with a single line i.e. ` M myfile.txt`.
-    .filter(|&(index, char)| index != 0 && char == ' ')
-    .map(|(index, _char)| index);
+  if !unselectable_regexes.is_empty() {
+    // put the unselectable lines back where they were, between the rows parsed
+    // from the lines around them
+    let mut rows = parsed.rows.into_iter().peekable();
+    let mut result = vec![];
+    for (index, line) in lines.iter().enumerate() {
+      if is_unselectable(index, line) {
+        result.push(Row {
+          original_line: line.to_string(),
+          cells: vec![line.to_string()],
+          unselectable: true,
+          ..Default::default()
+        });
+      } else if let Some(row) =
+        rows.next_if(|row| row.original_line.split('\n').next() == Some(**line))
+      {
+        result.push(row);
+      }
+    }
+    result.extend(rows);
+    parsed.rows = result;
+  }
 
-  let mut spaces_set: HashSet<usize> = HashSet::from_iter(spaces_iter);
+  if options.tree {
+    parsed.tree_column = tree::build(&mut parsed.rows);
+  }
 
-  for line in lines {
-    // TODO consider how to remove the .clone() here
-    for s_index in spaces_set.clone() {
-      for (index, char) in CharPosIter::new(line) {
-        if index == s_index && char != ' ' {
-          spaces_set.remove(&s_index);
-        }
+  Ok(parsed)
+}
+
+fn parse_rows(trimmed_text: String, options: &ParseOptions) -> Result<Parsed, String> {
+  match options.format {
+    Format::Table => {
+      if let Some(separator) = &options.record_separator {
+        return parse_records(&trimmed_text, separator, options);
+      }
+
+      if let Some(row_regex) = &options.row_regex {
+        return row_regex::parse(
+          trimmed_text.lines(),
+          row_regex,
+          options.show_unmatched_lines,
+        );
       }
+
+      let mut column_indices = vec![];
+      let mut rows = match &options.delimiter {
+        Some(delimiter) => delimited::parse(&trimmed_text, delimiter)?,
+        None => {
+          let lines = trimmed_text.lines().collect::<Vec<&str>>();
+          column_indices = if options.column_indices.is_empty() {
+            columns::get_column_indices(&lines, options.header)
+          } else {
+            options.column_indices.clone()
+          };
+          parse_table(&lines, &column_indices, options.header)
+        }
+      };
+
+      let column_names = if options.header && !rows.is_empty() {
+        let header = rows.remove(0);
+        header
+          .cells
+          .iter()
+          .map(|cell| cell.trim().to_owned())
+          .collect()
+      } else {
+        vec![]
+      };
+
+      Ok(Parsed {
+        column_names,
+        rows,
+        column_indices,
+        ..Default::default()
+      })
     }
+    Format::Json => json::parse(&trimmed_text, &options.columns),
   }
+}
+
+fn compile_regexes(regexes: &[String], setting: &str) -> Result<Vec<Regex>, String> {
+  regexes
+    .iter()
+    .map(|regex| {
+      Regex::new(regex).map_err(|error| format!("Invalid {} regex `{}`: {}", setting, regex, error))
+    })
+    .collect()
+}
 
-  let mut spaces = spaces_set.into_iter().collect::<Vec<usize>>();
-  spaces.sort();
+// The row_regex (if there is one) is matched against each whole record. Otherwise
+// the first line of each record is parsed as usual to get the cells.
+fn parse_records(
+  text: &str,
+  separator: &RecordSeparator,
+  options: &ParseOptions,
+) -> Result<Parsed, String> {
+  let records = records::split(text, separator)?;
 
-  let mut result = spaces
+  if let Some(row_regex) = &options.row_regex {
+    return row_regex::parse(
+      records.iter().map(String::as_str),
+      row_regex,
+      options.show_unmatched_lines,
+    );
+  }
+
+  let summaries = records
     .iter()
-    .enumerate()
-    .filter(|(index, position)| {
-      *index == spaces.len() - 1 || spaces[*index + 1] != (**position + 1)
+    .map(|record| records::summary(record))
+    .join("\n");
+  let mut parsed = parse_rows(
+    summaries,
+    &ParseOptions {
+      record_separator: None,
+      ..options.clone()
+    },
+  )?;
+
+  // there's one row per record, except for the header
+  let skip = if options.header { 1 } else { 0 };
+  for (row, record) in parsed.rows.iter_mut().zip(records.into_iter().skip(skip)) {
+    row.original_line = record;
+  }
+
+  Ok(parsed)
+}
+
+fn parse_table(lines: &[&str], column_indices: &[usize], header: bool) -> Vec<Row> {
+  let mut column_indices = column_indices.to_vec();
+  let column_sizes = column_indices
+    .iter()
+    .tuple_windows()
+    .map(|(i0, i1)| i1 - i0)
+    .chain(once(usize::MAX))
+    .collect::<Vec<_>>();
+  column_indices.push(usize::MAX);
+
+  let mut rows = lines
+    .iter()
+    .map(|line| {
+      // every char of a plain ASCII line takes up one terminal column, so we can slice it directly
+      let cells = if line.bytes().all(|b| b == b' ' || b.is_ascii_graphic()) {
+        column_indices
+          .iter()
+          .map(|start| cmp::min(*start, line.len()))
+          .tuple_windows()
+          .enumerate()
+          .map(|(column, (start, end))| trim_cell(column, &line[start..end]))
+          .collect()
+      } else {
+        column_sizes
+          .iter()
+          .scan(line.chars().peekable(), |chars, column_size| {
+            Some(take_width(chars, *column_size))
+          })
+          .enumerate()
+          .map(|(column, cell)| trim_cell(column, &cell))
+          .collect()
+      };
+
+      Row::new((*line).to_owned(), cells)
+    })
+    .collect::<Vec<Row>>();
+
+  trim_numeric_columns(&mut rows, header);
+
+  rows
+}
+
+// A value that overflows its column pushes the next one along, so that the next
+// column's cell can start with the space between the two. Leading spaces only
+// mean something in the first column, e.g. the ` M` from `git status --short`.
+fn trim_cell(column: usize, cell: &str) -> String {
+  if column == 0 {
+    cell.trim_end().to_owned()
+  } else {
+    cell.trim().to_owned()
+  }
+}
+
+// Right-aligned numbers are padded on the left, which isn't part of the value.
+// The header, if there is one, is left out of the decision.
+fn trim_numeric_columns(rows: &mut [Row], header: bool) {
+  let column_count = rows.iter().map(|row| row.cells.len()).max().unwrap_or(0);
+  let data_rows = &rows[cmp::min(header as usize, rows.len())..];
+
+  let numeric_columns = (0..column_count)
+    .filter(|&index| {
+      let values = data_rows
+        .iter()
+        .filter_map(|row| row.cells.get(index))
+        .filter(|cell| !cell.is_empty())
+        .collect::<Vec<&String>>();
+
+      !values.is_empty()
+        && values
+          .iter()
+          .all(|cell| columns::is_numeric(cell.trim_start()))
     })
-    .map(|(_index, position)| *position + 1)
     .collect::<Vec<usize>>();
 
-  result.insert(0, 0);
+  for row in rows.iter_mut() {
+    for &index in numeric_columns.iter() {
+      if let Some(cell) = row.cells.get_mut(index) {
+        *cell = cell.trim_start().to_owned();
+      }
+    }
+  }
+}
+
+// takes as many chars as fit in the given number of terminal columns. Zero-width
+// chars that follow are included, so that accents stay with their letters.
+fn take_width(chars: &mut Peekable<Chars>, width: usize) -> String {
+  let mut result = String::new();
+  let mut taken = 0;
+  while let Some(&c) = chars.peek() {
+    let char_width = c.width().unwrap_or(1);
+    if taken + char_width > width {
+      break;
+    }
+    taken += char_width;
+    result.push(c);
+    chars.next();
+  }
 
   result
 }
@@ -100,6 +467,12 @@ fn get_column_indices(text: &String) -> Vec<usize> {
 mod tests {
   use super::*;
   use pretty_assertions::assert_eq;
+  use tui::style::{Color, Modifier, Style};
+
+  fn parse_text_table(text: &str, header: bool) -> Vec<Row> {
+    let lines = text.lines().collect::<Vec<&str>>();
+    parse_table(&lines, &columns::get_column_indices(&lines, header), header)
+  }
 
   #[test]
   fn test_one_line_cut_short() {
@@ -108,7 +481,7 @@ mod tests {
                 col1\n";
 
     assert_eq!(
-      parse(String::from(text)),
+      parse_text_table(text, false),
       vec![
         Row {
           original_line: String::from("col1 col2 col3"),
@@ -117,6 +490,7 @@ mod tests {
             String::from("col2"),
             String::from("col3"),
           ],
+          ..Default::default()
         },
         Row {
           original_line: String::from("col1 col2 col3"),
@@ -125,10 +499,12 @@ mod tests {
             String::from("col2"),
             String::from("col3"),
           ],
+          ..Default::default()
         },
         Row {
           original_line: String::from("col1"),
           cells: vec![String::from("col1"), String::from(""), String::from(""),],
+          ..Default::default()
         },
       ],
     )
@@ -142,7 +518,7 @@ mod tests {
                 43484e7c2774   dd:latest                   \"ops/dev/proxy…\"     2 weeks ago   Up 46 seconds   0.0.0.0:80->80/tcp, 9400/tcp       blah-proxy_4\n\
                 8a61b6cc2d3b   aaaaa:4.0.3-alpine          \"docker.s…\"          2 weeks ago   Up 46 seconds   0.0.0.0:6300->6322/tcp             blah.99_1\n";
 
-    assert_eq!(parse(String::from(text)), vec![
+    assert_eq!(parse_text_table(text, false), vec![
           Row {
                 original_line: String::from("CONTAINER ID   IMAGE                       COMMAND              CREATED       STATUS          PORTS                              NAMES"),
                 cells: vec![
@@ -155,6 +531,7 @@ mod tests {
                     String::from("PORTS"),
                     String::from("NAMES"),
                 ],
+                ..Default::default()
             },
             Row {
                 original_line: String::from("17c523089229   aa                          \"./ops/dev/api\"      2 weeks ago   Up 43 seconds   0.0.0.0:20->80/tcp                 blah"),
@@ -168,6 +545,7 @@ mod tests {
                     String::from("0.0.0.0:20->80/tcp"),
                     String::from("blah"),
                 ],
+                ..Default::default()
             },
             Row {
                 original_line: String::from("dcddf219bb2b   bb                          \"./ops/dev/sid…\"     2 weeks ago   Up 44 seconds                                      blah-sidekiq_2"),
@@ -181,6 +559,7 @@ mod tests {
                     String::from(""),
                     String::from("blah-sidekiq_2"),
                 ],
+                ..Default::default()
             },
             Row {
                 original_line: String::from("43484e7c2774   dd:latest                   \"ops/dev/proxy…\"     2 weeks ago   Up 46 seconds   0.0.0.0:80->80/tcp, 9400/tcp       blah-proxy_4"),
@@ -194,6 +573,7 @@ mod tests {
                     String::from("0.0.0.0:80->80/tcp, 9400/tcp"),
                     String::from("blah-proxy_4"),
                 ],
+                ..Default::default()
             },
 
             Row {
@@ -208,27 +588,129 @@ mod tests {
                     String::from("0.0.0.0:6300->6322/tcp"),
                     String::from("blah.99_1"),
                 ],
+                ..Default::default()
             },
 
 
     ])
   }
 
+  #[test]
+  fn test_parse_with_header() {
+    let text = "CONTAINER ID   IMAGE       NAMES\n\
+                17c523089229   aa          blah\n\
+                dcddf219bb2b   bb:latest   blah-sidekiq_2\n";
+
+    let options = ParseOptions {
+      header: true,
+      ..Default::default()
+    };
+
+    assert_eq!(
+      parse(String::from(text), &options),
+      Ok(Parsed {
+        column_names: vec![
+          String::from("CONTAINER ID"),
+          String::from("IMAGE"),
+          String::from("NAMES"),
+        ],
+        rows: vec![
+          Row::new(
+            String::from("17c523089229   aa          blah"),
+            vec![
+              String::from("17c523089229"),
+              String::from("aa"),
+              String::from("blah"),
+            ],
+          ),
+          Row::new(
+            String::from("dcddf219bb2b   bb:latest   blah-sidekiq_2"),
+            vec![
+              String::from("dcddf219bb2b"),
+              String::from("bb:latest"),
+              String::from("blah-sidekiq_2"),
+            ],
+          ),
+        ],
+        column_indices: vec![0, 15, 27],
+        ..Default::default()
+      }),
+    )
+  }
+
+  #[test]
+  fn test_parse_more() {
+    let lines = |lines: &[&str]| {
+      lines
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>()
+    };
+    let options = ParseOptions::default();
+
+    let rows = parse_more(&lines(&["c   3"]), &options, &[0, 4]).unwrap();
+    assert_eq!(
+      rows.map(|rows| rows.into_iter().map(|row| row.cells).collect::<Vec<_>>()),
+      Some(vec![vec![String::from("c"), String::from("3")]])
+    );
+
+    // the value runs into the second column, so the columns need working out again
+    assert_eq!(
+      parse_more(&lines(&["dddddd 4"]), &options, &[0, 4]),
+      Ok(None)
+    );
+
+    let json_options = ParseOptions {
+      format: Format::Json,
+      ..Default::default()
+    };
+    assert_eq!(
+      parse_more(&lines(&["{\"a\": 1}"]), &json_options, &[]),
+      Ok(None)
+    );
+  }
+
+  #[test]
+  fn test_parse_csv_with_header() {
+    let options = ParseOptions {
+      delimiter: Some(Delimiter::Csv),
+      header: true,
+      ..Default::default()
+    };
+
+    assert_eq!(
+      parse(
+        String::from("PID,COMMAND\n1,\"/sbin/init splash\"\n"),
+        &options
+      ),
+      Ok(Parsed {
+        column_names: vec![String::from("PID"), String::from("COMMAND")],
+        rows: vec![Row::new(
+          String::from("1,\"/sbin/init splash\""),
+          vec![String::from("1"), String::from("/sbin/init splash")],
+        )],
+        ..Default::default()
+      }),
+    )
+  }
+
   #[test]
   fn test_parse_git_status() {
     let text = " M src/main.rs\n\
                 ?? src/parse/\n";
 
     assert_eq!(
-      parse(String::from(text)),
+      parse_text_table(text, false),
       vec![
         Row {
           original_line: String::from(" M src/main.rs"),
           cells: vec![String::from(" M"), String::from("src/main.rs"),],
+          ..Default::default()
         },
         Row {
           original_line: String::from("?? src/parse/"),
           cells: vec![String::from("??"), String::from("src/parse/"),],
+          ..Default::default()
         },
       ],
     )
@@ -239,14 +721,32 @@ mod tests {
     let text = " M src/main.rs\n";
 
     assert_eq!(
-      parse(String::from(text)),
+      parse_text_table(text, false),
       vec![Row {
         original_line: String::from(" M src/main.rs"),
         cells: vec![String::from(" M"), String::from("src/main.rs"),],
+        ..Default::default()
       },],
     )
   }
 
+  #[test]
+  fn test_first_line_counts_towards_numeric_columns_without_header() {
+    let text = " M a.rs\n 1 b.rs\n";
+
+    let cells = parse_text_table(text, false)
+      .into_iter()
+      .map(|row| row.cells)
+      .collect::<Vec<Vec<String>>>();
+    assert_eq!(
+      cells,
+      vec![
+        vec![String::from(" M"), String::from("a.rs")],
+        vec![String::from(" 1"), String::from("b.rs")],
+      ]
+    );
+  }
+
   #[test]
   fn test_parse_ls() {
     let text = "-rw-r--r--     1 user  staff     159 28 Apr  2020 Dockerfile\n\
@@ -254,7 +754,7 @@ mod tests {
                 drwxr-xr-x     3 user  staff      96 11 Apr  2020 docs";
 
     assert_eq!(
-      parse(String::from(text)),
+      parse_text_table(text, false),
       vec![
         Row {
           original_line: String::from(
@@ -265,12 +765,13 @@ mod tests {
             String::from("1"),
             String::from("user"),
             String::from("staff"),
-            String::from(" 159"),
+            String::from("159"),
             String::from("28"),
             String::from("Apr"),
-            String::from(" 2020"),
+            String::from("2020"),
             String::from("Dockerfile"),
           ],
+          ..Default::default()
         },
         Row {
           original_line: String::from(
@@ -287,6 +788,7 @@ mod tests {
             String::from("15:19"),
             String::from("Readme.md"),
           ],
+          ..Default::default()
         },
         Row {
           original_line: String::from("drwxr-xr-x     3 user  staff      96 11 Apr  2020 docs"),
@@ -295,14 +797,349 @@ mod tests {
             String::from("3"),
             String::from("user"),
             String::from("staff"),
-            String::from("  96"),
+            String::from("96"),
             String::from("11"),
             String::from("Apr"),
-            String::from(" 2020"),
+            String::from("2020"),
             String::from("docs"),
           ],
+          ..Default::default()
         },
       ],
     )
   }
+
+  // GNU ls pads columns by display width: `jürgen` is spelt with a combining
+  // diaeresis, `開発` and `日本` are two columns per char, and the emoji is a
+  // zero-width-joiner sequence.
+  #[test]
+  fn test_parse_ls_multilingual() {
+    let text = "-rw-r--r-- 1 ju\u{308}rgen 開発  1024 Jan  1 10:00 日本 語.txt\n\
+                -rw-r--r-- 1 jesse  staff   12 Jan  1 10:00 ab cd.txt\n\
+                drwxr-xr-x 2 jesse  staff   64 Jan  1 10:00 👩\u{200d}💻 code";
+
+    let rows = parse_text_table(text, false);
+
+    assert_eq!(
+      rows
+        .iter()
+        .map(|row| row.cells_as_strs())
+        .collect::<Vec<_>>(),
+      vec![
+        vec![
+          "-rw-r--r--",
+          "1",
+          "ju\u{308}rgen",
+          "開発",
+          "1024",
+          "Jan",
+          "1",
+          "10:00",
+          "日本 語.txt",
+        ],
+        vec![
+          "-rw-r--r--",
+          "1",
+          "jesse",
+          "staff",
+          "12",
+          "Jan",
+          "1",
+          "10:00",
+          "ab cd.txt",
+        ],
+        vec![
+          "drwxr-xr-x",
+          "2",
+          "jesse",
+          "staff",
+          "64",
+          "Jan",
+          "1",
+          "10:00",
+          "👩\u{200d}💻 code",
+        ],
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_ls_color() {
+    let text = "drwxr-xr-x 2 user staff 4096 Jan  1 10:00 \x1b[0m\x1b[01;34mdocs\x1b[0m\n\
+                -rwxr-xr-x 1 user staff  120 Jan  1 10:00 \x1b[01;32mbuild.sh\x1b[0m\n\
+                -rw-r--r-- 1 user staff   42 Jan  1 10:00 notes.txt\n";
+
+    let parsed = parse(String::from(text), &ParseOptions::default()).unwrap();
+
+    assert_eq!(
+      parsed.rows[0].cells_as_strs(),
+      vec![
+        "drwxr-xr-x",
+        "2",
+        "user",
+        "staff",
+        "4096",
+        "Jan",
+        "1",
+        "10:00",
+        "docs"
+      ]
+    );
+    assert_eq!(
+      parsed.rows[0].original_line,
+      "drwxr-xr-x 2 user staff 4096 Jan  1 10:00 docs"
+    );
+    assert_eq!(
+      parsed.rows[0].styled_cells[8],
+      Spans::from(Span::styled(
+        "docs",
+        Style::default()
+          .fg(Color::Blue)
+          .add_modifier(Modifier::BOLD)
+      ))
+    );
+    assert_eq!(parsed.rows[0].styled_cells[0], Spans::from("drwxr-xr-x"));
+    assert!(parsed.rows[2].styled_cells.is_empty());
+  }
+
+  // Real-world outputs in `fixtures/`, each next to the cells we expect to get
+  // from it (one row per line, cells separated by tabs).
+  #[test]
+  fn test_parse_fixtures() {
+    let fixtures = [
+      (
+        "df_h",
+        include_str!("fixtures/df_h.txt"),
+        include_str!("fixtures/df_h.cells"),
+        true,
+      ),
+      (
+        "docker_ps",
+        include_str!("fixtures/docker_ps.txt"),
+        include_str!("fixtures/docker_ps.cells"),
+        true,
+      ),
+      (
+        "free",
+        include_str!("fixtures/free.txt"),
+        include_str!("fixtures/free.cells"),
+        true,
+      ),
+      (
+        "git_status_short",
+        include_str!("fixtures/git_status_short.txt"),
+        include_str!("fixtures/git_status_short.cells"),
+        false,
+      ),
+      (
+        "kubectl_get_pods",
+        include_str!("fixtures/kubectl_get_pods.txt"),
+        include_str!("fixtures/kubectl_get_pods.cells"),
+        true,
+      ),
+      (
+        "ls_l",
+        include_str!("fixtures/ls_l.txt"),
+        include_str!("fixtures/ls_l.cells"),
+        false,
+      ),
+      (
+        "lsblk",
+        include_str!("fixtures/lsblk.txt"),
+        include_str!("fixtures/lsblk.cells"),
+        true,
+      ),
+      (
+        "netstat",
+        include_str!("fixtures/netstat.txt"),
+        include_str!("fixtures/netstat.cells"),
+        true,
+      ),
+      (
+        "ps_aux",
+        include_str!("fixtures/ps_aux.txt"),
+        include_str!("fixtures/ps_aux.cells"),
+        true,
+      ),
+      (
+        "ps_pid_rss",
+        include_str!("fixtures/ps_pid_rss.txt"),
+        include_str!("fixtures/ps_pid_rss.cells"),
+        true,
+      ),
+    ];
+
+    for (name, text, cells, header) in fixtures.iter() {
+      let parsed = parse_text_table(text, *header)
+        .into_iter()
+        .map(|row| row.cells)
+        .collect::<Vec<Vec<String>>>();
+      let expected = cells
+        .lines()
+        .map(|line| line.split('\t').map(String::from).collect())
+        .collect::<Vec<Vec<String>>>();
+
+      assert_eq!(parsed, expected, "fixture `{}`", name);
+    }
+  }
+
+  #[test]
+  fn test_parse_records() {
+    let text = "commit 1a2b\n\
+                Author: Jesse\n\
+                \n    Fix bug\n\
+                \n\
+                commit 3c4d\n\
+                Author: Jesse\n\
+                \n    Add feature";
+    let options = ParseOptions {
+      record_separator: Some(RecordSeparator::Regex(String::from("^commit "))),
+      ..Default::default()
+    };
+
+    let parsed = parse(String::from(text), &options).unwrap();
+
+    assert_eq!(
+      parsed.rows,
+      vec![
+        Row::new(
+          String::from("commit 1a2b\nAuthor: Jesse\n\n    Fix bug"),
+          vec![String::from("commit"), String::from("1a2b")],
+        ),
+        Row::new(
+          String::from("commit 3c4d\nAuthor: Jesse\n\n    Add feature"),
+          vec![String::from("commit"), String::from("3c4d")],
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_records_with_row_regex() {
+    let text =
+      "commit 1a2b\nAuthor: Jesse\n\n    Fix bug\n\ncommit 3c4d\nAuthor: Ann\n\n    Add feature";
+    let options = ParseOptions {
+      record_separator: Some(RecordSeparator::Regex(String::from("^commit "))),
+      row_regex: Some(String::from(
+        r"^commit (?P<sha>\w+)\nAuthor: (?P<author>.*)\n\n\s+(?P<subject>.*)",
+      )),
+      ..Default::default()
+    };
+
+    let parsed = parse(String::from(text), &options).unwrap();
+
+    assert_eq!(parsed.column_names, vec!["sha", "author", "subject"]);
+    assert_eq!(
+      parsed
+        .rows
+        .iter()
+        .map(|row| row.cells_as_strs())
+        .collect::<Vec<Vec<&str>>>(),
+      vec![["1a2b", "Jesse", "Fix bug"], ["3c4d", "Ann", "Add feature"]]
+    );
+  }
+
+  #[test]
+  fn test_parse_tree() {
+    let text = ".\n\
+                ├── src\n\
+                │   ├── main.rs\n\
+                │   └── parse\n\
+                └── Cargo.toml\n";
+    let options = ParseOptions {
+      tree: true,
+      ..Default::default()
+    };
+
+    let parsed = parse(String::from(text), &options).unwrap();
+
+    assert_eq!(parsed.tree_column, Some(0));
+    assert_eq!(
+      parsed
+        .rows
+        .iter()
+        .map(|row| (row.depth, row.cells_as_strs()))
+        .collect::<Vec<(usize, Vec<&str>)>>(),
+      vec![
+        (0, vec!["."]),
+        (1, vec!["src"]),
+        (2, vec!["main.rs"]),
+        (2, vec!["parse"]),
+        (1, vec!["Cargo.toml"]),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_skip_and_ignore_lines() {
+    let text = "Filesystem  Size  Used\n\
+                total 48\n\
+                /dev/vda    252G   18G\n\
+                tmpfs       3.0G     0\n\
+                total       255G   18G\n";
+    let options = ParseOptions {
+      header: true,
+      skip_trailing_lines: 1,
+      ignore_lines_matching: vec![String::from(r"^total \d+$")],
+      ..Default::default()
+    };
+
+    let parsed = parse(String::from(text), &options).unwrap();
+
+    assert_eq!(
+      parsed
+        .rows
+        .iter()
+        .map(|row| row.cells_as_strs())
+        .collect::<Vec<Vec<&str>>>(),
+      vec![["/dev/vda", "252G", "18G"], ["tmpfs", "3.0G", "0"]]
+    );
+  }
+
+  #[test]
+  fn test_unselectable_lines() {
+    let text = "Changes to be committed:\n\
+                \tmodified:   src/app.rs\n\
+                \n\
+                Untracked files:\n\
+                \tsrc/tree.rs\n";
+    let options = ParseOptions {
+      ignore_lines_matching: vec![String::from(r"^\s*$")],
+      unselectable_lines_matching: vec![String::from(r"^\S")],
+      ..Default::default()
+    };
+
+    let parsed = parse(String::from(text), &options).unwrap();
+
+    assert_eq!(
+      parsed
+        .rows
+        .iter()
+        .map(|row| (row.unselectable, row.original_line.as_str()))
+        .collect::<Vec<(bool, &str)>>(),
+      vec![
+        (true, "Changes to be committed:"),
+        (false, "\tmodified:   src/app.rs"),
+        (true, "Untracked files:"),
+        (false, "\tsrc/tree.rs"),
+      ]
+    );
+    assert_eq!(
+      parsed.rows[0].cells,
+      vec![String::from("Changes to be committed:")]
+    );
+  }
+
+  #[test]
+  fn test_invalid_ignore_regex() {
+    let options = ParseOptions {
+      ignore_lines_matching: vec![String::from("(")],
+      ..Default::default()
+    };
+
+    assert!(matches!(
+      parse(String::from("a"), &options),
+      Err(e) if e.starts_with("Invalid ignore_lines_matching regex `(`"),
+    ));
+  }
 }
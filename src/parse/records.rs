@@ -0,0 +1,102 @@
+use regex::Regex;
+
+use crate::config::RecordSeparator;
+
+// Splits the text into records of one or more lines. Blank lines at the ends of
+// a record aren't part of it, and records with nothing in them are dropped.
+pub fn split(text: &str, separator: &RecordSeparator) -> Result<Vec<String>, String> {
+  let is_boundary: Box<dyn Fn(&str) -> bool> = match separator {
+    RecordSeparator::BlankLine => Box::new(|line| line.trim().is_empty()),
+    RecordSeparator::Regex(regex) => {
+      let regex = Regex::new(regex)
+        .map_err(|error| format!("Invalid record_separator regex `{}`: {}", regex, error))?;
+      Box::new(move |line| regex.is_match(line))
+    }
+    RecordSeparator::Indentation => {
+      Box::new(|line| !line.trim().is_empty() && !line.starts_with(char::is_whitespace))
+    }
+  };
+
+  let mut records = vec![];
+  let mut current = vec![];
+  for line in text.lines() {
+    if is_boundary(line) {
+      records.extend(finish_record(&current));
+      current.clear();
+    }
+    current.push(line);
+  }
+  records.extend(finish_record(&current));
+
+  Ok(records)
+}
+
+fn finish_record(lines: &[&str]) -> Option<String> {
+  let is_blank = |line: &&str| line.trim().is_empty();
+  let start = lines.iter().position(|line| !is_blank(line))?;
+  let end = lines.iter().rposition(|line| !is_blank(line))? + 1;
+
+  Some(lines[start..end].join("\n"))
+}
+
+// the line shown in the table for a record
+pub fn summary(record: &str) -> &str {
+  record.lines().next().unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_split_on_blank_lines() {
+    let text = "Name: api\nStatus: Running\n\n\nName: worker\nStatus: Pending\n";
+
+    assert_eq!(
+      split(text, &RecordSeparator::BlankLine).unwrap(),
+      vec![
+        "Name: api\nStatus: Running",
+        "Name: worker\nStatus: Pending"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_split_on_regex() {
+    let text = "commit 1a2b\nAuthor: Jesse\n\n    Fix bug\n\ncommit 3c4d\nAuthor: Jesse\n\n    Add feature\n";
+    let separator = RecordSeparator::Regex(String::from("^commit "));
+
+    assert_eq!(
+      split(text, &separator).unwrap(),
+      vec![
+        "commit 1a2b\nAuthor: Jesse\n\n    Fix bug",
+        "commit 3c4d\nAuthor: Jesse\n\n    Add feature"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_split_on_indentation() {
+    let text =
+      "1: lo: <LOOPBACK,UP>\n    inet 127.0.0.1/8\n2: eth0: <BROADCAST>\n    inet 10.0.0.5/24\n";
+
+    assert_eq!(
+      split(text, &RecordSeparator::Indentation).unwrap(),
+      vec![
+        "1: lo: <LOOPBACK,UP>\n    inet 127.0.0.1/8",
+        "2: eth0: <BROADCAST>\n    inet 10.0.0.5/24"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_invalid_regex() {
+    let separator = RecordSeparator::Regex(String::from("("));
+
+    assert!(matches!(
+      split("", &separator),
+      Err(e) if e.starts_with("Invalid record_separator regex `(`"),
+    ));
+  }
+}
@@ -0,0 +1,118 @@
+use regex::Regex;
+
+use super::{Parsed, Row};
+
+// Each capture group of the regex becomes a column, named after the group (or
+// its number, for unnamed groups). Lines that don't match are dropped, unless
+// `show_unmatched_lines` is set, in which case they're kept as unselectable rows.
+// When the output is split into records, each record is matched as a whole.
+pub fn parse<'a>(
+  lines: impl Iterator<Item = &'a str>,
+  row_regex: &str,
+  show_unmatched_lines: bool,
+) -> Result<Parsed, String> {
+  let regex = Regex::new(row_regex)
+    .map_err(|error| format!("Invalid row_regex `{}`: {}", row_regex, error))?;
+
+  let column_names = regex
+    .capture_names()
+    .enumerate()
+    .skip(1)
+    .map(|(index, name)| match name {
+      Some(name) => name.to_owned(),
+      None => index.to_string(),
+    })
+    .collect();
+
+  let rows = lines
+    .filter_map(|line| match regex.captures(line) {
+      Some(captures) => Some(Row::new(
+        line.to_owned(),
+        captures
+          .iter()
+          .skip(1)
+          .map(|capture| capture.map(|c| c.as_str().to_owned()).unwrap_or_default())
+          .collect(),
+      )),
+      None if show_unmatched_lines => Some(Row {
+        original_line: line.to_owned(),
+        cells: vec![line.to_owned()],
+        unselectable: true,
+        ..Default::default()
+      }),
+      None => None,
+    })
+    .collect();
+
+  Ok(Parsed {
+    column_names,
+    rows,
+    ..Default::default()
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  static TEXT: &str = "Jan 01 10:00:01 web nginx[123]: started\n\
+                       -- Boot 2f3c --\n\
+                       Jan 01 10:00:02 web sshd[456]: accepted";
+
+  static ROW_REGEX: &str =
+    r"^(?P<time>\w+ \d+ [\d:]+) (\S+) (?P<unit>\w+)\[(?P<pid>\d+)\]: (?P<message>.*)$";
+
+  #[test]
+  fn test_named_groups_become_columns() {
+    let parsed = parse(TEXT.lines(), ROW_REGEX, false).unwrap();
+
+    assert_eq!(
+      parsed.column_names,
+      vec!["time", "2", "unit", "pid", "message"]
+    );
+    assert_eq!(
+      parsed.rows,
+      vec![
+        Row::new(
+          String::from("Jan 01 10:00:01 web nginx[123]: started"),
+          ["Jan 01 10:00:01", "web", "nginx", "123", "started"]
+            .iter()
+            .map(|s| String::from(*s))
+            .collect(),
+        ),
+        Row::new(
+          String::from("Jan 01 10:00:02 web sshd[456]: accepted"),
+          ["Jan 01 10:00:02", "web", "sshd", "456", "accepted"]
+            .iter()
+            .map(|s| String::from(*s))
+            .collect(),
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_show_unmatched_lines() {
+    let parsed = parse(TEXT.lines(), ROW_REGEX, true).unwrap();
+
+    assert_eq!(
+      parsed.rows[1],
+      Row {
+        original_line: String::from("-- Boot 2f3c --"),
+        cells: vec![String::from("-- Boot 2f3c --")],
+        unselectable: true,
+        ..Default::default()
+      }
+    );
+    assert!(!parsed.rows[0].unselectable);
+  }
+
+  #[test]
+  fn test_invalid_regex() {
+    assert!(matches!(
+      parse(TEXT.lines(), "(", false),
+      Err(e) if e.starts_with("Invalid row_regex `(`"),
+    ));
+  }
+}
@@ -0,0 +1,164 @@
+use super::Row;
+
+// Works out how rows nest, for output like `tree`, `ps --forest`, `lsblk` or
+// `cargo tree`. Nesting shows up as a prefix of spaces and/or branch characters
+// (`├──`, `│`, `└─┬`, `\_`) in one of the columns. We strip that prefix from the
+// cell and treat each row as the child of the closest row above it with a
+// shorter prefix. Returns the column the tree is in, if we found one.
+pub fn build(rows: &mut [Row]) -> Option<usize> {
+  let column = tree_column(rows)?;
+
+  // the prefix width and index of each row on the path to the current row
+  let mut ancestors: Vec<(usize, usize)> = vec![];
+  for index in 0..rows.len() {
+    let (prefix_len, prefix_width) = match rows[index].cells.get(column) {
+      Some(cell) => prefix(cell),
+      None => (0, 0),
+    };
+    if prefix_len > 0 {
+      rows[index].cells[column].replace_range(..prefix_len, "");
+    }
+
+    while matches!(ancestors.last(), Some((width, _)) if *width >= prefix_width) {
+      ancestors.pop();
+    }
+
+    rows[index].depth = ancestors.len();
+    if let Some((_, parent)) = ancestors.last() {
+      rows[index].parent_cells = rows[*parent].cells.clone();
+    }
+    ancestors.push((prefix_width, index));
+  }
+
+  Some(column)
+}
+
+// The first column with branch characters in it. Failing that, the first column
+// where some of the values are indented.
+fn tree_column(rows: &[Row]) -> Option<usize> {
+  let column_count = rows.iter().map(|row| row.cells.len()).max().unwrap_or(0);
+  let prefixes = |column: usize| {
+    rows
+      .iter()
+      .filter_map(move |row| row.cells.get(column))
+      .map(|cell| &cell[..prefix(cell).0])
+  };
+
+  (0..column_count)
+    .find(|&column| prefixes(column).any(|prefix| prefix.contains(|c| c != ' ')))
+    .or_else(|| (0..column_count).find(|&column| prefixes(column).any(|prefix| !prefix.is_empty())))
+}
+
+// The length in bytes and the width in terminal columns of the cell's tree
+// prefix. ASCII characters like `|` and `-` only count when they're drawing a
+// branch, so that values like `-rw-r--r--` aren't mistaken for one.
+fn prefix(cell: &str) -> (usize, usize) {
+  let mut width = 0;
+  let mut in_branch = false;
+  let mut chars = cell.char_indices().peekable();
+  while let Some((index, c)) = chars.next() {
+    let next = chars.peek().map(|(_, c)| *c);
+    let is_prefix = match c {
+      ' ' => true,
+      '\u{2500}'..='\u{257f}' => true,
+      '|' | '`' => next == Some(' ') || next == Some('-'),
+      '\\' => next == Some('_'),
+      '+' => next == Some('-'),
+      '_' | '-' => in_branch,
+      _ => false,
+    };
+    if !is_prefix {
+      return (index, width);
+    }
+
+    in_branch = c != ' ';
+    width += 1;
+  }
+
+  (cell.len(), width)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn rows(cells: &[&[&str]]) -> Vec<Row> {
+    cells
+      .iter()
+      .map(|cells| {
+        Row::new(
+          cells.join(" "),
+          cells.iter().map(|cell| String::from(*cell)).collect(),
+        )
+      })
+      .collect()
+  }
+
+  fn summary(rows: &[Row]) -> Vec<(usize, String, String)> {
+    rows
+      .iter()
+      .map(|row| {
+        (
+          row.depth,
+          row.cells.last().cloned().unwrap_or_default(),
+          row.parent_cells.first().cloned().unwrap_or_default(),
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_box_drawing() {
+    let mut rows = rows(&[
+      &["."],
+      &["├── src"],
+      &["│   ├── main.rs"],
+      &["│   └── parse"],
+      &["│       └── mod.rs"],
+      &["└── Cargo.toml"],
+    ]);
+
+    assert_eq!(build(&mut rows), Some(0));
+    assert_eq!(
+      summary(&rows),
+      vec![
+        (0, String::from("."), String::from("")),
+        (1, String::from("src"), String::from(".")),
+        (2, String::from("main.rs"), String::from("src")),
+        (2, String::from("parse"), String::from("src")),
+        (3, String::from("mod.rs"), String::from("parse")),
+        (1, String::from("Cargo.toml"), String::from(".")),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_ps_forest() {
+    let mut rows = rows(&[
+      &["1234", "Ss", "bash"],
+      &["5678", "S+", " \\_ vim notes.txt"],
+      &["5680", "S+", "     \\_ /bin/sh -c make"],
+      &["5690", "R+", " \\_ ps f"],
+    ]);
+
+    assert_eq!(build(&mut rows), Some(2));
+    assert_eq!(
+      summary(&rows),
+      vec![
+        (0, String::from("bash"), String::from("")),
+        (1, String::from("vim notes.txt"), String::from("1234")),
+        (2, String::from("/bin/sh -c make"), String::from("5678")),
+        (1, String::from("ps f"), String::from("1234")),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_flat_output() {
+    let mut rows = rows(&[&["-rw-r--r--", "a.txt"], &["drwxr-xr-x", "src"]]);
+
+    assert_eq!(build(&mut rows), None);
+    assert!(rows.iter().all(|row| row.depth == 0));
+  }
+}
@@ -0,0 +1,78 @@
+use std::collections::VecDeque;
+
+// Remembers the output of the most recently run display commands so that
+// scrolling back over rows we've already seen doesn't mean running them again.
+// Only output from commands that succeeded is kept, since a failure (like a
+// timeout) may well not happen again.
+pub struct PreviewCache {
+  capacity: usize,
+  // most recently used entries are at the back
+  entries: VecDeque<(String, String)>,
+}
+
+impl PreviewCache {
+  pub fn new(capacity: usize) -> PreviewCache {
+    PreviewCache {
+      capacity,
+      entries: VecDeque::new(),
+    }
+  }
+
+  pub fn get(&mut self, command: &str) -> Option<String> {
+    let index = self.entries.iter().position(|(c, _)| c == command)?;
+    let entry = self.entries.remove(index)?;
+    let result = entry.1.clone();
+    self.entries.push_back(entry);
+
+    Some(result)
+  }
+
+  pub fn insert(&mut self, command: String, output: String) {
+    self.remove(&command);
+    self.entries.push_back((command, output));
+
+    while self.entries.len() > self.capacity {
+      self.entries.pop_front();
+    }
+  }
+
+  pub fn remove(&mut self, command: &str) {
+    self.entries.retain(|(c, _)| c != command);
+  }
+
+  pub fn clear(&mut self) {
+    self.entries.clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_evicts_least_recently_used() {
+    let mut cache = PreviewCache::new(2);
+    cache.insert(String::from("a"), String::from("1"));
+    cache.insert(String::from("b"), String::from("2"));
+
+    // reading `a` makes `b` the least recently used
+    assert_eq!(cache.get("a"), Some(String::from("1")));
+    cache.insert(String::from("c"), String::from("3"));
+
+    assert_eq!(cache.get("b"), None);
+    assert_eq!(cache.get("a"), Some(String::from("1")));
+    assert_eq!(cache.get("c"), Some(String::from("3")));
+  }
+
+  #[test]
+  fn test_insert_replaces_existing_entry() {
+    let mut cache = PreviewCache::new(2);
+    cache.insert(String::from("a"), String::from("1"));
+    cache.insert(String::from("a"), String::from("2"));
+    cache.insert(String::from("b"), String::from("3"));
+
+    assert_eq!(cache.get("a"), Some(String::from("2")));
+    assert_eq!(cache.get("b"), Some(String::from("3")));
+  }
+}
@@ -1,8 +1,19 @@
+use std::{
+  cmp,
+  collections::HashSet,
+  ops::{Range, RangeInclusive},
+};
 use tui::widgets::TableState;
 
 pub struct StatefulTable {
   pub state: TableState,
   pub row_count: usize,
+  // where the cursor was when visual mode was entered. None if we're not in visual mode.
+  pub visual_anchor: Option<usize>,
+  // indices of rows that the cursor skips over
+  pub unselectable: HashSet<usize>,
+  // the index of the first row on screen
+  pub offset: usize,
 }
 
 impl StatefulTable {
@@ -10,18 +21,22 @@ impl StatefulTable {
     StatefulTable {
       state: TableState::default(),
       row_count,
+      visual_anchor: None,
+      unselectable: HashSet::new(),
+      offset: 0,
     }
   }
 
+  pub fn visual_range(&self) -> Option<RangeInclusive<usize>> {
+    let anchor = self.visual_anchor?;
+    let selected = self.state.selected()?;
+
+    Some(cmp::min(anchor, selected)..=cmp::max(anchor, selected))
+  }
+
   pub fn next(&mut self) {
     let i = match self.state.selected() {
-      Some(i) => {
-        if self.row_count == 0 || i >= self.row_count - 1 {
-          i
-        } else {
-          i + 1
-        }
-      }
+      Some(i) => self.next_selectable(i).unwrap_or(i),
       None => 0,
     };
     self.state.select(Some(i));
@@ -29,15 +44,86 @@ impl StatefulTable {
 
   pub fn previous(&mut self) {
     let i = match self.state.selected() {
-      Some(i) => {
-        if i == 0 {
-          i
-        } else {
-          i - 1
-        }
-      }
+      Some(i) => self.previous_selectable(i).unwrap_or(i),
       None => 0,
     };
     self.state.select(Some(i));
   }
+
+  // moves the cursor off an unselectable row, preferring to move down
+  pub fn avoid_unselectable(&mut self) {
+    if let Some(i) = self.state.selected() {
+      if self.unselectable.contains(&i) {
+        let i = self
+          .next_selectable(i)
+          .or_else(|| self.previous_selectable(i))
+          .unwrap_or(i);
+        self.state.select(Some(i));
+      }
+    }
+  }
+
+  // The rows that fit on screen, scrolling just enough to keep the cursor in view.
+  // We only hand these rows to tui so that drawing doesn't slow down with the
+  // size of the output.
+  pub fn visible_rows(&mut self, height: usize) -> Range<usize> {
+    let selected = self.state.selected().unwrap_or(0);
+    if selected < self.offset {
+      self.offset = selected;
+    } else if height > 0 && selected >= self.offset + height {
+      self.offset = selected + 1 - height;
+    }
+    self.offset = cmp::min(self.offset, self.row_count.saturating_sub(height));
+
+    self.offset..cmp::min(self.offset + height, self.row_count)
+  }
+
+  fn next_selectable(&self, i: usize) -> Option<usize> {
+    (i + 1..self.row_count).find(|i| !self.unselectable.contains(i))
+  }
+
+  fn previous_selectable(&self, i: usize) -> Option<usize> {
+    (0..i).rev().find(|i| !self.unselectable.contains(i))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_cursor_skips_unselectable_rows() {
+    let mut table = StatefulTable::new(4);
+    table.unselectable = [1, 3].iter().cloned().collect();
+    table.next();
+
+    table.next();
+    assert_eq!(table.state.selected(), Some(2));
+    // there's nothing selectable below, so we stay put
+    table.next();
+    assert_eq!(table.state.selected(), Some(2));
+    table.previous();
+    assert_eq!(table.state.selected(), Some(0));
+  }
+
+  #[test]
+  fn test_visible_rows_follow_cursor() {
+    let mut table = StatefulTable::new(10);
+    table.state.select(Some(0));
+    assert_eq!(table.visible_rows(4), 0..4);
+
+    table.state.select(Some(5));
+    assert_eq!(table.visible_rows(4), 2..6);
+    // moving back up within the window doesn't scroll
+    table.state.select(Some(3));
+    assert_eq!(table.visible_rows(4), 2..6);
+    table.state.select(Some(1));
+    assert_eq!(table.visible_rows(4), 1..5);
+
+    // the window shrinks to fit when rows go away
+    table.row_count = 3;
+    table.state.select(Some(2));
+    assert_eq!(table.visible_rows(4), 0..3);
+  }
 }
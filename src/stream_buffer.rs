@@ -0,0 +1,95 @@
+use std::collections::VecDeque;
+
+// The lines we've read so far from a command that keeps running. Once we have
+// more than `max_rows` lines we drop the oldest ones, except for the leading
+// lines (skipped lines and the header) which we need in order to parse the rest.
+pub struct StreamBuffer {
+  head_len: usize,
+  max_rows: Option<usize>,
+  head: Vec<String>,
+  lines: VecDeque<String>,
+}
+
+impl StreamBuffer {
+  pub fn new(head_len: usize, max_rows: Option<usize>) -> StreamBuffer {
+    StreamBuffer {
+      head_len,
+      max_rows,
+      head: vec![],
+      lines: VecDeque::new(),
+    }
+  }
+
+  pub fn extend(&mut self, lines: Vec<String>) {
+    for line in lines {
+      if self.head.len() < self.head_len {
+        self.head.push(line);
+        continue;
+      }
+
+      self.lines.push_back(line);
+      if let Some(max_rows) = self.max_rows {
+        if self.lines.len() > max_rows {
+          self.lines.pop_front();
+        }
+      }
+    }
+  }
+
+  // whether that many more lines would all be rows, with none of them going into
+  // the head or pushing old rows out
+  pub fn appends_cleanly(&self, count: usize) -> bool {
+    self.head.len() == self.head_len
+      && self
+        .max_rows
+        .is_none_or(|max_rows| self.lines.len() + count <= max_rows)
+  }
+
+  pub fn text(&self) -> String {
+    self
+      .head
+      .iter()
+      .chain(self.lines.iter())
+      .map(|line| line.as_ref())
+      .collect::<Vec<&str>>()
+      .join("\n")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn lines(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|line| String::from(*line)).collect()
+  }
+
+  #[test]
+  fn test_unbounded() {
+    let mut buffer = StreamBuffer::new(0, None);
+    buffer.extend(lines(&["a", "b"]));
+    buffer.extend(lines(&["c"]));
+
+    assert_eq!(buffer.text(), "a\nb\nc");
+  }
+
+  #[test]
+  fn test_drops_oldest_rows_but_keeps_head() {
+    let mut buffer = StreamBuffer::new(1, Some(2));
+    buffer.extend(lines(&["NAME  STATUS", "a     Running"]));
+    buffer.extend(lines(&["b     Running", "c     Pending"]));
+
+    assert_eq!(buffer.text(), "NAME  STATUS\nb     Running\nc     Pending");
+  }
+
+  #[test]
+  fn test_appends_cleanly() {
+    let mut buffer = StreamBuffer::new(1, Some(2));
+    assert!(!buffer.appends_cleanly(1));
+
+    buffer.extend(lines(&["NAME  STATUS", "a     Running"]));
+    assert!(buffer.appends_cleanly(1));
+    assert!(!buffer.appends_cleanly(2));
+  }
+}
@@ -1,39 +1,520 @@
 use regex::{Captures, Regex};
+use std::sync::OnceLock;
 
-use crate::{config::Command, parse::Row};
+use crate::{
+  command::CommandLine,
+  config::{Command, CommandTemplate},
+  parse::{json, Row},
+};
 
-pub fn resolve_command(command: &dyn Command, row: &Row) -> String {
-  // if keybinding has a regex we need to use that, otherwise we generate the regex ourselves
-  let matches = match &command.regex() {
-    Some(regex) => {
-      let regex = Regex::new(regex).unwrap(); // TODO: handle malformed regex
-      match regex.captures(&row.original_line) {
-        None => vec![],
-        Some(captures) => captures
-          .iter()
-          .map(|capture| match capture {
-            Some(capture) => capture.as_str(),
-            None => "",
-          })
-          .collect::<Vec<&str>>(),
+pub enum Placeholder<'a> {
+  // `$0`, `$1`, etc
+  Index(usize),
+  // `${CONTAINER ID}`, resolved against the column names
+  Name(&'a str),
+  // `${.path.to.field}`, resolved against the original JSON object
+  JsonPath(&'a str),
+  // `$parent.0` or `${parent.PID}`, resolved against the row's parent in tree mode
+  Parent(Box<Placeholder<'a>>),
+}
+
+pub fn resolve_command(
+  command: &dyn Command,
+  row: &Row,
+  column_names: &[String],
+) -> Result<CommandLine, String> {
+  resolve_command_for_rows(command, &[row], column_names)
+}
+
+// one command for each row, compiling the command's regex just the once
+pub fn resolve_command_for_each_row(
+  command: &dyn Command,
+  rows: &[&Row],
+  column_names: &[String],
+) -> Result<Vec<CommandLine>, String> {
+  let regex = compile_regex(command)?;
+  rows
+    .iter()
+    .map(|row| resolve(command, regex.as_ref(), &[row], column_names))
+    .collect()
+}
+
+// Each placeholder is expanded to the value from every row, quoted and separated
+// by spaces, e.g. `rm $0` becomes `rm a.txt b.txt 'c d.txt'`.
+pub fn resolve_command_for_rows(
+  command: &dyn Command,
+  rows: &[&Row],
+  column_names: &[String],
+) -> Result<CommandLine, String> {
+  resolve(
+    command,
+    compile_regex(command)?.as_ref(),
+    rows,
+    column_names,
+  )
+}
+
+fn resolve(
+  command: &dyn Command,
+  regex: Option<&Regex>,
+  rows: &[&Row],
+  column_names: &[String],
+) -> Result<CommandLine, String> {
+  let matches_by_row = rows
+    .iter()
+    .map(|row| matches(regex, row))
+    .collect::<Vec<Vec<&str>>>();
+
+  let resolve = |placeholder: Placeholder| {
+    rows
+      .iter()
+      .zip(matches_by_row.iter())
+      .map(|(row, matches)| resolve_placeholder(&placeholder, row, matches, column_names))
+      .collect()
+  };
+
+  match command.command() {
+    CommandTemplate::Shell(template) => template_replace(template, resolve).map(CommandLine::Shell),
+    CommandTemplate::Exec(argv) => resolve_argv(argv, &resolve).map(CommandLine::Exec),
+  }
+}
+
+// There's no shell to quote for, so values are substituted as-is. An argument
+// that's nothing but a placeholder becomes one argument per row, e.g. `[rm, $0]`
+// becomes `[rm, a.txt, c d.txt]`.
+fn resolve_argv(argv: &[String], resolve: &Resolve) -> Result<Vec<String>, String> {
+  let regex = placeholder_regex();
+
+  let mut result = vec![];
+  for arg in argv {
+    match regex.captures(arg) {
+      Some(captures) if captures.get(0).unwrap().as_str() == arg => {
+        result.extend(resolve(placeholder(&captures))?)
       }
+      _ => result.push(replace_placeholders(arg, resolve, false)?),
     }
+  }
+
+  if result.first().is_none_or(|program| program.is_empty()) {
+    return Err(String::from("The command has no program to run"));
+  }
+
+  Ok(result)
+}
+
+fn compile_regex(command: &dyn Command) -> Result<Option<Regex>, String> {
+  command
+    .regex()
+    .map(|regex| Regex::new(regex).map_err(|error| format!("Invalid regex `{}`: {}", regex, error)))
+    .transpose()
+}
+
+fn matches<'a>(regex: Option<&Regex>, row: &'a Row) -> Vec<&'a str> {
+  // if keybinding has a regex we need to use that, otherwise we generate the regex ourselves
+  match regex {
+    Some(regex) => match regex.captures(&row.original_line) {
+      None => vec![],
+      Some(captures) => captures
+        .iter()
+        .map(|capture| match capture {
+          Some(capture) => capture.as_str(),
+          None => "",
+        })
+        .collect::<Vec<&str>>(),
+    },
     None => row.cells_as_strs(),
-  };
+  }
+}
+
+fn resolve_placeholder(
+  placeholder: &Placeholder,
+  row: &Row,
+  matches: &[&str],
+  column_names: &[String],
+) -> Result<String, String> {
+  match *placeholder {
+    Placeholder::Index(index) => Ok(matches.get(index).unwrap_or(&"").to_string()),
+    Placeholder::Name(name) => match column_names.iter().position(|c| c == name) {
+      Some(index) => Ok(row.cells.get(index).cloned().unwrap_or_default()),
+      None => Err(unknown_column_error(name, column_names)),
+    },
+    Placeholder::JsonPath(path) => Ok(
+      row
+        .json
+        .as_ref()
+        .and_then(|value| json::lookup(value, path))
+        .map(json::to_cell)
+        .unwrap_or_default(),
+    ),
+    Placeholder::Parent(ref placeholder) => {
+      if row.parent_cells.is_empty() {
+        return Err(String::from("The selected row has no parent"));
+      }
+
+      match **placeholder {
+        Placeholder::Index(index) => Ok(row.parent_cells.get(index).cloned().unwrap_or_default()),
+        Placeholder::Name(name) => match column_names.iter().position(|c| c == name) {
+          Some(index) => Ok(row.parent_cells.get(index).cloned().unwrap_or_default()),
+          None => Err(unknown_column_error(name, column_names)),
+        },
+        _ => Err(String::from(
+          "`parent` can only be followed by a column number or name",
+        )),
+      }
+    }
+  }
+}
 
-  template_replace(&command.command(), &matches)
+fn unknown_column_error(name: &str, column_names: &[String]) -> String {
+  if column_names.is_empty() {
+    format!(
+      "Unknown column `{}`: no column names are available. Set `header: true` in your profile if the first line of output is a header.",
+      name
+    )
+  } else {
+    format!(
+      "Unknown column `{}`. Available columns: {}",
+      name,
+      column_names
+        .iter()
+        .map(|c| format!("`{}`", c))
+        .collect::<Vec<String>>()
+        .join(", ")
+    )
+  }
 }
 
 // adapted from https://stackoverflow.com/questions/53974404/replacing-numbered-placeholders-with-elements-of-a-vector-in-rust
-pub fn template_replace(template: &str, values: &[&str]) -> String {
-  let regex = Regex::new(r#"\$(\d+)"#).unwrap();
-  regex
-    .replace_all(template, |captures: &Captures| {
-      values.get(index(captures)).unwrap_or(&"")
-    })
-    .to_string()
+// We can't use regex.replace_all here because resolving a placeholder can fail.
+// Values are quoted for the shell unless the placeholder starts with a `!`, e.g.
+// `$!0` or `${!NAMES}`, in which case they're inserted as-is.
+pub fn template_replace<F>(template: &str, resolve: F) -> Result<String, String>
+where
+  F: Fn(Placeholder) -> Result<Vec<String>, String>,
+{
+  replace_placeholders(template, &resolve, true)
+}
+
+type Resolve<'r> = dyn Fn(Placeholder) -> Result<Vec<String>, String> + 'r;
+
+// compiled once, since commands are resolved for every row on every draw
+fn placeholder_regex() -> &'static Regex {
+  static REGEX: OnceLock<Regex> = OnceLock::new();
+  REGEX.get_or_init(|| Regex::new(r#"\$(?:(!)?(\d+)|(!)?parent\.(\d+)|\{(!)?([^}]*)\})"#).unwrap())
+}
+
+fn replace_placeholders(template: &str, resolve: &Resolve, quote: bool) -> Result<String, String> {
+  let regex = placeholder_regex();
+
+  let mut result = String::new();
+  let mut last_end = 0;
+  for captures in regex.captures_iter(template) {
+    let whole_match = captures.get(0).unwrap();
+    result.push_str(&template[last_end..whole_match.start()]);
+
+    let values = resolve(placeholder(&captures))?;
+    let is_raw = [1, 3, 5].iter().any(|group| captures.get(*group).is_some());
+    let substitution = values
+      .iter()
+      .map(|value| {
+        if is_raw || !quote {
+          value.to_owned()
+        } else {
+          shell_escape(value)
+        }
+      })
+      .collect::<Vec<String>>()
+      .join(" ");
+    result.push_str(&substitution);
+
+    last_end = whole_match.end();
+  }
+  result.push_str(&template[last_end..]);
+
+  Ok(result)
 }
 
-fn index(captures: &Captures) -> usize {
-  captures.get(1).unwrap().as_str().parse().unwrap()
+fn placeholder<'a>(captures: &Captures<'a>) -> Placeholder<'a> {
+  if let Some(index) = captures.get(2) {
+    return Placeholder::Index(index.as_str().parse().unwrap());
+  }
+  if let Some(index) = captures.get(4) {
+    return Placeholder::Parent(Box::new(Placeholder::Index(
+      index.as_str().parse().unwrap(),
+    )));
+  }
+
+  named_placeholder(captures.get(6).unwrap().as_str())
+}
+
+fn named_placeholder(name: &str) -> Placeholder<'_> {
+  if let Some(name) = name.strip_prefix("parent.") {
+    return Placeholder::Parent(Box::new(named_placeholder(name)));
+  }
+  if let Ok(index) = name.parse() {
+    // `${1}` is the same as `$1`
+    return Placeholder::Index(index);
+  }
+
+  match name.strip_prefix('.') {
+    Some(path) => Placeholder::JsonPath(path),
+    None => Placeholder::Name(name),
+  }
+}
+
+// Wraps the value in single quotes so that the shell treats it as one literal
+// word. Values made up entirely of harmless characters are left alone so that
+// commands like `git checkout main` still read naturally in the UI.
+pub fn shell_escape(value: &str) -> String {
+  let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./:,@%+=".contains(c);
+
+  if !value.is_empty() && value.chars().all(is_safe) {
+    return value.to_owned();
+  }
+
+  format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{command, config::KeyBinding};
+  use pretty_assertions::assert_eq;
+
+  fn binding(command: &str) -> KeyBinding {
+    KeyBinding {
+      command: CommandTemplate::from(command),
+      ..Default::default()
+    }
+  }
+
+  fn docker_row() -> Row {
+    Row::new(
+      String::from("17c523089229   aa   blah"),
+      vec![
+        String::from("17c523089229"),
+        String::from("aa"),
+        String::from("blah"),
+      ],
+    )
+  }
+
+  fn docker_column_names() -> Vec<String> {
+    vec![
+      String::from("CONTAINER ID"),
+      String::from("IMAGE"),
+      String::from("NAMES"),
+    ]
+  }
+
+  #[test]
+  fn test_positional_placeholders() {
+    assert_eq!(
+      resolve_command(&binding("docker stop $0 $2"), &docker_row(), &[]),
+      Ok(CommandLine::from("docker stop 17c523089229 blah"))
+    );
+  }
+
+  #[test]
+  fn test_named_placeholders() {
+    assert_eq!(
+      resolve_command(
+        &binding("docker stop ${CONTAINER ID} ${NAMES}"),
+        &docker_row(),
+        &docker_column_names()
+      ),
+      Ok(CommandLine::from("docker stop 17c523089229 blah"))
+    );
+  }
+
+  #[test]
+  fn test_unknown_named_placeholder() {
+    assert_eq!(
+      resolve_command(
+        &binding("docker stop ${ID}"),
+        &docker_row(),
+        &docker_column_names()
+      ),
+      Err(String::from(
+        "Unknown column `ID`. Available columns: `CONTAINER ID`, `IMAGE`, `NAMES`"
+      ))
+    );
+  }
+
+  #[test]
+  fn test_malformed_regex() {
+    let binding = KeyBinding {
+      regex: Some(String::from("(unclosed")),
+      ..binding("docker stop $1")
+    };
+
+    assert!(resolve_command(&binding, &docker_row(), &[])
+      .unwrap_err()
+      .starts_with("Invalid regex `(unclosed`"));
+  }
+
+  #[test]
+  fn test_raw_placeholders() {
+    let row = Row::new(
+      String::from("a b"),
+      vec![String::from("a b"), String::from("*.txt")],
+    );
+
+    assert_eq!(
+      resolve_command(&binding("echo $!0 ${!1}"), &row, &[]),
+      Ok(CommandLine::from("echo a b *.txt"))
+    );
+  }
+
+  #[test]
+  fn test_shell_escape() {
+    let cases = vec![
+      ("main", "main"),
+      ("src/main.rs", "src/main.rs"),
+      ("", "''"),
+      ("my file.txt", "'my file.txt'"),
+      ("it's", "'it'\\''s'"),
+      ("\"quoted\"", "'\"quoted\"'"),
+      ("*.rs", "'*.rs'"),
+      ("line1\nline2", "'line1\nline2'"),
+      ("$(rm -rf ~)", "'$(rm -rf ~)'"),
+      ("`whoami`", "'`whoami`'"),
+      ("foo; rm -rf ~", "'foo; rm -rf ~'"),
+    ];
+
+    for (value, expected) in cases {
+      assert_eq!(shell_escape(value), expected);
+    }
+  }
+
+  // the real test: does the shell hand the value back to us untouched?
+  #[test]
+  fn test_shell_escape_round_trip() {
+    let values = vec![
+      "my file.txt",
+      "it's a \"test\"",
+      "*",
+      "line1\nline2",
+      "$(echo pwned)",
+      "`echo pwned`",
+      "$HOME",
+      "foo; echo pwned",
+      "foo && echo pwned | cat > /dev/null",
+      "\\ backslash",
+    ];
+
+    for value in values {
+      let row = Row::new(String::from(value), vec![String::from(value)]);
+      let command = resolve_command(&binding("printf %s $0"), &row, &[]).unwrap();
+
+      assert_eq!(
+        command::run_command(&command, None),
+        Ok(String::from(value))
+      );
+    }
+  }
+
+  #[test]
+  fn test_resolve_command_for_rows() {
+    let rows = [
+      Row::new(String::from("a.txt"), vec![String::from("a.txt")]),
+      Row::new(String::from("c d.txt"), vec![String::from("c d.txt")]),
+    ];
+
+    assert_eq!(
+      resolve_command_for_rows(&binding("rm $0"), &rows.iter().collect::<Vec<&Row>>(), &[]),
+      Ok(CommandLine::from("rm a.txt 'c d.txt'"))
+    );
+  }
+
+  #[test]
+  fn test_resolve_command_for_each_row() {
+    let rows = [
+      Row::new(String::from("a.txt 1"), vec![]),
+      Row::new(String::from("b.txt 2"), vec![]),
+    ];
+    let binding = KeyBinding {
+      regex: Some(String::from(r"^(\S+) (\d+)$")),
+      ..binding("head -n $2 $1")
+    };
+
+    assert_eq!(
+      resolve_command_for_each_row(&binding, &rows.iter().collect::<Vec<&Row>>(), &[]),
+      Ok(vec![
+        CommandLine::from("head -n 1 a.txt"),
+        CommandLine::from("head -n 2 b.txt"),
+      ])
+    );
+  }
+
+  #[test]
+  fn test_exec_placeholders_are_separate_arguments() {
+    let binding = KeyBinding {
+      command: CommandTemplate::Exec(vec![
+        String::from("rm"),
+        String::from("--"),
+        String::from("$0"),
+        String::from("name=$0"),
+      ]),
+      ..Default::default()
+    };
+    let rows = [
+      Row::new(String::from("a.txt"), vec![String::from("a.txt")]),
+      Row::new(String::from("c 'd'.txt"), vec![String::from("c 'd'.txt")]),
+    ];
+
+    assert_eq!(
+      resolve_command_for_rows(&binding, &rows.iter().collect::<Vec<&Row>>(), &[]),
+      Ok(CommandLine::Exec(vec![
+        String::from("rm"),
+        String::from("--"),
+        String::from("a.txt"),
+        String::from("c 'd'.txt"),
+        String::from("name=a.txt c 'd'.txt"),
+      ]))
+    );
+  }
+
+  #[test]
+  fn test_exec_without_program() {
+    let binding = KeyBinding {
+      command: CommandTemplate::Exec(vec![String::from("$3")]),
+      ..Default::default()
+    };
+
+    assert_eq!(
+      resolve_command(&binding, &docker_row(), &[]),
+      Err(String::from("The command has no program to run"))
+    );
+  }
+
+  #[test]
+  fn test_named_placeholder_without_header() {
+    assert!(matches!(
+      resolve_command(&binding("docker stop ${ID}"), &docker_row(), &[]),
+      Err(e) if e.contains("header: true"),
+    ));
+  }
+
+  #[test]
+  fn test_parent_placeholders() {
+    let row = Row {
+      parent_cells: vec![String::from("1234"), String::from("bash")],
+      ..docker_row()
+    };
+    let column_names = [String::from("PID"), String::from("COMMAND")];
+
+    assert_eq!(
+      resolve_command(
+        &binding("kill $parent.0 ${parent.PID} $0"),
+        &row,
+        &column_names
+      ),
+      Ok(CommandLine::from("kill 1234 1234 17c523089229"))
+    );
+    assert_eq!(
+      resolve_command(&binding("kill $parent.0"), &docker_row(), &[]),
+      Err(String::from("The selected row has no parent"))
+    );
+  }
 }
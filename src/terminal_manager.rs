@@ -3,11 +3,38 @@ use crossterm::{
   execute,
   terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::{error::Error, io::stdout};
+use std::{
+  error::Error,
+  io::stdout,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  thread,
+  time::Duration,
+};
 use tui::{backend::CrosstermBackend, Terminal};
 
 pub struct TerminalManager {
   pub terminal: tui::Terminal<tui::backend::CrosstermBackend<std::io::Stdout>>,
+  pub input_pause: Arc<InputPause>,
+}
+
+// While a child process has the terminal, the thread polling for keypresses needs
+// to stay out of its way, otherwise it will steal input intended for the child.
+#[derive(Default)]
+pub struct InputPause {
+  requested: AtomicBool,
+  acknowledged: AtomicBool,
+}
+
+impl InputPause {
+  // called by the polling thread before each poll. Returns true if it should not poll.
+  pub fn check(&self) -> bool {
+    let requested = self.requested.load(Ordering::SeqCst);
+    self.acknowledged.store(requested, Ordering::SeqCst);
+    requested
+  }
 }
 
 // TODO: see if this is the right approach. Perhaps our perhaps we should have a prepare() function pulled out of the new() function
@@ -20,7 +47,10 @@ impl TerminalManager {
     let mut terminal = Terminal::new(backend)?;
     terminal.clear()?;
 
-    Ok(TerminalManager { terminal })
+    Ok(TerminalManager {
+      terminal,
+      input_pause: Arc::new(InputPause::default()),
+    })
   }
 
   pub fn teardown(&mut self) -> Result<(), Box<dyn Error>> {
@@ -33,4 +63,29 @@ impl TerminalManager {
     // TODO: understand why this works
     Ok(self.terminal.show_cursor()?)
   }
+
+  // hands the terminal back to the user's shell so that a child process can use it
+  pub fn suspend(&mut self) -> Result<(), Box<dyn Error>> {
+    self.input_pause.requested.store(true, Ordering::SeqCst);
+    while !self.input_pause.acknowledged.load(Ordering::SeqCst) {
+      thread::sleep(Duration::from_millis(10));
+    }
+
+    self.teardown()
+  }
+
+  pub fn resume(&mut self) -> Result<(), Box<dyn Error>> {
+    enable_raw_mode()?;
+    execute!(
+      self.terminal.backend_mut(),
+      EnterAlternateScreen,
+      EnableMouseCapture
+    )?;
+    // the alternate screen has been wiped so we need tui to redraw everything
+    self.terminal.clear()?;
+
+    self.input_pause.requested.store(false, Ordering::SeqCst);
+
+    Ok(())
+  }
 }
@@ -1,6 +1,7 @@
 use crate::{
   app::{App, FocusedPanel},
-  config::Profile,
+  command,
+  config::{Multi, Profile},
   template,
 };
 
@@ -9,7 +10,9 @@ pub fn display_keybindings(profile: Option<&Profile>, app: &App) -> String {
   let panel_keybindings = match app.focused_panel {
     FocusedPanel::Table => {
       let mut keybindings = vec![format!(
-        "▲/▼/j/k: navigate, /: filter, esc: clear filter, q: quit, $: open config file (open {})",
+        "▲/▼/j/k: navigate, {}/: filter, space: mark, V: visual mode, esc: clear marks/filter, {}ctrl+t: jobs, q: quit, $: open config file (open {})",
+        if app.tree_column.is_some() { "◀/▶: fold/unfold, " } else { "" },
+        if command::is_running() || app.jobs.is_busy() { "ctrl+x: cancel command, " } else { "" },
         app.config_path.to_str().unwrap()
       )];
 
@@ -21,6 +24,17 @@ pub fn display_keybindings(profile: Option<&Profile>, app: &App) -> String {
     FocusedPanel::ConfirmationPopup(_) => {
       vec![String::from("enter: run command, esc: cancel, q: quit")]
     }
+    FocusedPanel::Jobs => vec![String::from(
+      "▲/▼/j/k: navigate, enter: show output, r: run again, x: cancel, esc: close, q: quit",
+    )],
+    FocusedPanel::OutputPopup(ref view) | FocusedPanel::JobOutputPopup(ref view)
+      if view.search.is_some() =>
+    {
+      vec![String::from("enter: search, esc: cancel search")]
+    }
+    FocusedPanel::OutputPopup(_) | FocusedPanel::JobOutputPopup(_) => vec![String::from(
+      "▲/▼/j/k: scroll, pgup/pgdn: page, g/G: top/bottom, /: search, n/N: next/previous match, esc: close popup, q: quit",
+    )],
   };
 
   panel_keybindings
@@ -36,12 +50,25 @@ fn profile_keybindings(profile: Option<&Profile>, app: &App) -> Vec<String> {
       _ => match app.get_selected_row() {
         Some(row) => {
           let mut result = vec![format!("Keybindings for profile '{}':", profile.name)];
+          // resolving every marked row on every frame would be too slow, so we
+          // show the selected row's command and how many more rows there are
+          let more = app.mark_count().saturating_sub(1);
 
           result.extend(
             profile
               .key_bindings
               .iter()
-              .map(|kb| format!("{}: {}", kb.key, template::resolve_command(kb, &row)))
+              .map(|kb| {
+                let command = match template::resolve_command(kb, row, &app.column_names) {
+                  Ok(command) if more == 0 => command.to_string(),
+                  Ok(command) => match kb.multi {
+                    Multi::Each => format!("{} (and {} more)", command, more),
+                    Multi::Join => format!("{} (joined with {} more)", command, more),
+                  },
+                  Err(error) => format!("<{}>", error),
+                };
+                format!("{}: {}", kb.key, command)
+              })
               .collect::<Vec<String>>(),
           );
 
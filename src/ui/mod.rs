@@ -1,20 +1,23 @@
 mod keybindings;
 
 use crate::{
+  ansi,
   app::{App, FocusedPanel},
-  parse,
+  jobs::{self, JobStatus},
 };
-use std::{cmp, time::SystemTime};
+use std::time::{Instant, SystemTime};
 use tui::{
   backend::Backend,
   layout::{Alignment, Constraint, Direction, Layout, Rect},
   style::{Color, Modifier, Style},
-  widgets::{Block, Borders, Cell, Paragraph, Row, Table, Wrap},
+  text::{Span, Spans, Text},
+  widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState, Wrap},
   Frame,
 };
+use unicode_width::UnicodeWidthStr;
 
 pub fn draw<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
-  let formatted_bindings = keybindings::display_keybindings(app.profile, &app);
+  let formatted_bindings = keybindings::display_keybindings(app.profile, app);
   let formatted_keybindings_height = formatted_bindings.lines().count() as u16;
 
   let rects = Layout::default()
@@ -36,12 +39,7 @@ pub fn draw<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
   draw_error_popup(app, frame);
   draw_confirmation_popup(app, frame);
 
-  let right_panel_percentage_width =
-    if app.profile.is_some() && app.profile.unwrap().display_command.is_some() {
-      50
-    } else {
-      0
-    };
+  let right_panel_percentage_width = if app.has_preview() { 50 } else { 0 };
 
   {
     let rects = Layout::default()
@@ -70,6 +68,10 @@ pub fn draw<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
       draw_keybindings(rects[2], frame, formatted_bindings);
     }
   }
+
+  // drawn last so that they cover the table and the preview
+  draw_jobs_panel(app, frame);
+  draw_output_popup(app, frame);
 }
 
 fn draw_error_popup<B: Backend>(app: &mut App, frame: &mut tui::Frame<B>) {
@@ -94,27 +96,153 @@ fn draw_error_popup<B: Backend>(app: &mut App, frame: &mut tui::Frame<B>) {
 }
 
 fn draw_confirmation_popup<B: Backend>(app: &mut App, frame: &mut tui::Frame<B>) {
-  if let FocusedPanel::ConfirmationPopup(command) = &app.focused_panel {
-    let popup = centered_rect(60, 20, frame.size());
-    let paragraph = Paragraph::new(format!(
-      "Are you sure you want to run command: `{}`?",
-      command
-    ))
-    .style(
+  if let FocusedPanel::ConfirmationPopup(batch) = &app.focused_panel {
+    let commands = &batch.commands;
+    let (text, height) = match commands.as_slice() {
+      [command] => (
+        format!("Are you sure you want to run command: `{}`?", command),
+        20,
+      ),
+      _ => (
+        format!(
+          "Are you sure you want to run the following {} commands?\n\n{}",
+          commands.len(),
+          commands
+            .iter()
+            .map(|command| format!("`{}`", command))
+            .collect::<Vec<String>>()
+            .join("\n")
+        ),
+        60,
+      ),
+    };
+    let popup = centered_rect(60, height, frame.size());
+    let paragraph = Paragraph::new(text)
+      .style(
+        Style::default()
+          .fg(Color::Reset)
+          .add_modifier(Modifier::BOLD),
+      )
+      .block(
+        Block::default()
+          .title("Confirm")
+          .borders(Borders::ALL)
+          .style(Style::default().fg(Color::Reset)),
+      )
+      .alignment(Alignment::Left)
+      .wrap(Wrap { trim: true });
+    frame.render_widget(paragraph, popup);
+  }
+}
+
+fn draw_output_popup<B: Backend>(app: &mut App, frame: &mut tui::Frame<B>) {
+  if let FocusedPanel::OutputPopup(view) | FocusedPanel::JobOutputPopup(view) =
+    &mut app.focused_panel
+  {
+    let popup = centered_rect(80, 80, frame.size());
+    // the borders take up a line each
+    view.height = popup.height.saturating_sub(2) as usize;
+
+    let lines = view
+      .lines
+      .iter()
+      .skip(view.scroll)
+      .take(view.height)
+      .map(|line| {
+        let spans = ansi::parse_lines(line)
+          .into_iter()
+          .next()
+          .unwrap_or_default();
+        let mut spans = Spans::from(spans);
+        if view.is_match(line) {
+          for span in spans.0.iter_mut() {
+            span.style = span.style.bg(Color::Yellow).fg(Color::Black);
+          }
+        }
+        spans
+      })
+      .collect::<Vec<Spans>>();
+
+    let title = match &view.search {
+      Some(search) => format!("Output (search: {})", search),
+      None if !view.query.is_empty() => format!(
+        "Output ({} match(es) for `{}`)",
+        view.match_indices().len(),
+        view.query
+      ),
+      None => String::from("Output"),
+    };
+
+    let paragraph = Paragraph::new(Text::from(lines))
+      .style(Style::default().fg(Color::Reset))
+      .block(Block::default().title(title).borders(Borders::ALL));
+    frame.render_widget(Clear, popup);
+    frame.render_widget(paragraph, popup);
+  }
+}
+
+fn draw_jobs_panel<B: Backend>(app: &mut App, frame: &mut tui::Frame<B>) {
+  if app.focused_panel != FocusedPanel::Jobs {
+    return;
+  }
+
+  let now = Instant::now();
+  let rows = app.jobs.jobs.iter().map(|job| {
+    let (status, color) = match job.status {
+      JobStatus::Queued => ("queued", Color::Reset),
+      JobStatus::Running => ("running", Color::Yellow),
+      JobStatus::Succeeded => ("done", Color::Green),
+      JobStatus::Failed => ("failed", Color::LightRed),
+      JobStatus::TimedOut => ("timed out", Color::LightRed),
+      JobStatus::Cancelled => ("cancelled", Color::DarkGray),
+    };
+    let command = match job.commands.as_slice() {
+      [command] => command.to_string(),
+      [first, rest @ ..] => format!("{} (and {} more)", first, rest.len()),
+      [] => String::from(""),
+    };
+    let duration = job
+      .duration(now)
+      .map(jobs::format_duration)
+      .unwrap_or_default();
+    let exit_code = match job.exit_code() {
+      Some(code) if job.status.is_finished() => code.to_string(),
+      _ => String::from(""),
+    };
+
+    Row::new(vec![
+      Cell::from(status),
+      Cell::from(duration),
+      Cell::from(exit_code),
+      Cell::from(command),
+    ])
+    .style(Style::default().fg(color))
+  });
+
+  let widths = [
+    Constraint::Length(9),
+    Constraint::Length(7),
+    Constraint::Length(4),
+    Constraint::Percentage(100),
+  ];
+  let table = Table::new(rows)
+    .header(
+      Row::new(vec!["STATUS", "TIME", "EXIT", "COMMAND"])
+        .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(Block::default().title("Jobs").borders(Borders::ALL))
+    .highlight_style(
       Style::default()
-        .fg(Color::Reset)
+        .bg(Color::Blue)
         .add_modifier(Modifier::BOLD),
     )
-    .block(
-      Block::default()
-        .title("Confirm")
-        .borders(Borders::ALL)
-        .style(Style::default().fg(Color::Reset)),
-    )
-    .alignment(Alignment::Left)
-    .wrap(Wrap { trim: true });
-    frame.render_widget(paragraph, popup);
-  }
+    .highlight_symbol("> ")
+    .widths(&widths)
+    .column_spacing(2);
+
+  let popup = centered_rect(80, 60, frame.size());
+  frame.render_widget(Clear, popup);
+  frame.render_stateful_widget(table, popup, &mut app.jobs.table.state);
 }
 
 fn draw_table<B: Backend>(app: &mut App, rect: Rect, frame: &mut tui::Frame<B>) {
@@ -126,21 +254,101 @@ fn draw_table<B: Backend>(app: &mut App, rect: Rect, frame: &mut tui::Frame<B>)
     Style::default()
   };
 
-  let filtered_rows = app.filtered_rows();
-  let rows = filtered_rows.iter().map(|row| {
-    let cells = row.cells.iter().map(|c| Cell::from(c.clone()));
-    Row::new(cells).height(1)
+  // we only show the marker column once something has been marked
+  let show_marks = app.has_marks();
+
+  // the header takes up a line
+  let height = if app.column_names.is_empty() {
+    rect.height
+  } else {
+    rect.height.saturating_sub(1)
+  };
+  let visible_rows = app.table.visible_rows(height as usize);
+  let rows = visible_rows.clone().map(|index| {
+    let row = app.filtered_row(index).unwrap();
+    let is_marked = app.is_marked(index);
+    let tree_prefix = |column: usize| match app.tree_column {
+      Some(tree_column) if tree_column == column => Some(app.tree_prefix(index)),
+      _ => None,
+    };
+    let mut cells = if row.styled_cells.is_empty() {
+      row
+        .cells
+        .iter()
+        .enumerate()
+        .map(|(column, c)| match tree_prefix(column) {
+          Some(prefix) => Cell::from(prefix + c),
+          None => Cell::from(c.clone()),
+        })
+        .collect::<Vec<Cell>>()
+    } else {
+      row
+        .styled_cells
+        .iter()
+        .enumerate()
+        .map(|(column, spans)| {
+          let mut spans = spans.clone();
+          if let Some(prefix) = tree_prefix(column) {
+            spans.0.insert(0, Span::raw(prefix));
+          }
+          Cell::from(spans)
+        })
+        .collect::<Vec<Cell>>()
+    };
+
+    if show_marks {
+      cells.insert(0, Cell::from(if is_marked { "●" } else { " " }));
+    }
+
+    let style = if row.unselectable {
+      Style::default().fg(Color::DarkGray)
+    } else if is_marked {
+      Style::default().fg(Color::Yellow)
+    } else {
+      Style::default()
+    };
+
+    Row::new(cells).style(style).height(1)
   });
 
-  let widths = get_column_widths(&filtered_rows);
+  let mut widths = app
+    .column_widths
+    .iter()
+    .map(|width| Constraint::Length(*width as u16))
+    .collect::<Vec<Constraint>>();
+  if show_marks {
+    widths.insert(0, Constraint::Length(1));
+  }
 
-  let table = Table::new(rows)
+  let mut table = Table::new(rows)
     .highlight_style(selected_style)
     .highlight_symbol("> ")
     .widths(&widths)
     .column_spacing(2);
 
-  frame.render_stateful_widget(table, rect, &mut app.table.state);
+  if !app.column_names.is_empty() {
+    let marker_cell = if show_marks {
+      Some(Cell::from(""))
+    } else {
+      None
+    };
+    let header_cells = marker_cell
+      .into_iter()
+      .chain(app.column_names.iter().map(|name| Cell::from(name.clone())));
+    table =
+      table.header(Row::new(header_cells).style(Style::default().add_modifier(Modifier::BOLD)));
+  }
+
+  // tui only sees the visible rows, so the selection is relative to the first of them
+  let mut state = TableState::default();
+  state.select(
+    app
+      .table
+      .state
+      .selected()
+      .map(|selected| selected.saturating_sub(visible_rows.start)),
+  );
+  frame.render_stateful_widget(table, rect, &mut state);
 }
 
 fn draw_keybindings<B: Backend>(rect: Rect, frame: &mut tui::Frame<B>, formatted_bindings: String) {
@@ -152,10 +360,11 @@ fn draw_keybindings<B: Backend>(rect: Rect, frame: &mut tui::Frame<B>, formatted
 
 fn draw_status_bar<B: Backend>(app: &mut App, rect: Rect, frame: &mut tui::Frame<B>) {
   let status_text = match app.status_text.as_ref() {
-    Some(text) => match text {
-      _ => format!("{} {}", spinner_frame(), text),
+    Some(text) => format!("{} {}", spinner_frame(), text),
+    None => match app.jobs.summary() {
+      Some(summary) => format!("{} {}", spinner_frame(), summary),
+      None => app.command_result.clone().unwrap_or_default(),
     },
-    None => String::from(""),
   };
 
   let status_bar = Paragraph::new(status_text).style(Style::default().fg(Color::Cyan));
@@ -169,7 +378,7 @@ fn draw_search_bar<B: Backend>(app: &mut App, rect: Rect, frame: &mut tui::Frame
   // Make the cursor visible and ask tui-rs to put it at the specified coordinates after rendering
   frame.set_cursor(
     // Put cursor past the end of the input text
-    rect.x + app.filter_text.len() as u16 + prefix.len() as u16,
+    rect.x + app.filter_text.width() as u16 + prefix.len() as u16,
     // Move one line down, from the border to the input line
     rect.y,
   );
@@ -181,37 +390,14 @@ fn draw_search_bar<B: Backend>(app: &mut App, rect: Rect, frame: &mut tui::Frame
 }
 
 fn draw_item_render<B: Backend>(app: &mut App, rect: Rect, frame: &mut tui::Frame<B>) {
-  let paragraph =
-    Paragraph::new(app.selected_item_content.as_ref()).style(Style::default().fg(Color::Reset));
+  let paragraph = match &app.selected_item_content {
+    Ok(content) => Paragraph::new(ansi::to_text(content)).style(Style::default().fg(Color::Reset)),
+    Err(error) => Paragraph::new(ansi::to_text(error)).style(Style::default().fg(Color::LightRed)),
+  };
 
   frame.render_widget(paragraph, rect);
 }
 
-fn get_column_widths(rows: &Vec<&parse::Row>) -> std::vec::Vec<tui::layout::Constraint> {
-  if rows.len() == 0 {
-    return vec![];
-  }
-
-  rows
-    .iter()
-    .map(|row| row.cells.iter().map(|cell| cell.len()).collect())
-    .fold(
-      std::iter::repeat(0)
-        .take(rows[0].cells.len())
-        .collect::<Vec<usize>>(),
-      |acc: Vec<usize>, curr: Vec<usize>| {
-        acc
-          .into_iter()
-          .zip(curr.into_iter())
-          .map(|(a, b)| cmp::max(a, b))
-          .collect()
-      },
-    )
-    .into_iter()
-    .map(|width| Constraint::Length(width as u16))
-    .collect::<Vec<Constraint>>()
-}
-
 static SPINNER_STATES: &[char] = &['⣾', '⣷', '⣯', '⣟', '⡿', '⢿', '⣻', '⣽'];
 
 fn spinner_frame() -> String {